@@ -107,8 +107,8 @@ fn do_part2(input: Vec<usize>) {
     // an alternative would be to reverse engineer the machine execution
     // or implement something like SAT solver
     // But even puzzle authors imply you should just try to bruteforce
-    let part2_answer_vec: Vec<usize> = (0..99)
-        .flat_map(|noun| (0..99).map(move |verb| (noun, verb)))
+    let part2_answer_vec: Vec<usize> = (0..=99)
+        .flat_map(|noun| (0..=99).map(move |verb| (noun, verb)))
         .map(|noun_verb_pair| {
             let machine_input = prepare_tape(input.clone(), noun_verb_pair);
             (noun_verb_pair, IntcodeMachine::new(machine_input).run())
@@ -0,0 +1,2 @@
+pub mod intcode_machine;
+pub mod utils;
@@ -12,7 +12,7 @@ pub fn num_to_digits_vec(val: usize) -> Vec<usize> {
     digits
 }
 
-pub fn digits_vec_to_num(digits: &Vec<usize>) -> usize {
+pub fn digits_vec_to_num(digits: &[usize]) -> usize {
     digits
         .iter()
         .cloned()
@@ -22,3 +22,10 @@ pub fn digits_vec_to_num(digits: &Vec<usize>) -> usize {
         })
         .unwrap()
 }
+
+pub fn read_input_file(path: &str) -> Vec<isize> {
+    match intcode_loader::load_program(path) {
+        Ok(program) => program,
+        Err(err) => panic!("could not load {}: {}", path, err),
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+
+use crate::intcode_machine::{
+    IntcodeMachine, IntcodeMachineError, MachineInput, MachineOutput, Tape,
+};
+use crate::topology::TopologyError;
+
+struct LoopState {
+    // signals not yet read by each of the amplifiers
+    queues: Vec<VecDeque<isize>>,
+    finished: Vec<bool>,
+    waiting: Vec<bool>,
+    deadlocked: bool,
+}
+
+impl LoopState {
+    fn previous(&self, amplifier: usize) -> usize {
+        (amplifier + self.queues.len() - 1) % self.queues.len()
+    }
+
+    // a woken up amplifier still counts as waiting until it gets to check its queue again
+    fn is_stuck(&self, amplifier: usize) -> bool {
+        self.waiting[amplifier]
+            && self.queues[amplifier].is_empty()
+            && !self.finished[self.previous(amplifier)]
+    }
+
+    fn all_stuck(&self) -> bool {
+        (0..self.queues.len()).all(|i| self.finished[i] || self.is_stuck(i))
+    }
+}
+
+// Signals passed around the loop. Every amplifier reads from its own queue, fed by the
+// previous amplifier only. Once all of the amplifiers that are still running wait for
+// a signal, none of them can ever get one, so they are all woken up to report a deadlock.
+struct SignalBus {
+    state: Mutex<LoopState>,
+    signal_ready: Condvar,
+}
+
+impl SignalBus {
+    fn new(num_amplifiers: usize) -> Self {
+        SignalBus {
+            state: Mutex::new(LoopState {
+                queues: vec![VecDeque::new(); num_amplifiers],
+                finished: vec![false; num_amplifiers],
+                waiting: vec![false; num_amplifiers],
+                deadlocked: false,
+            }),
+            signal_ready: Condvar::new(),
+        }
+    }
+
+    fn send(&self, amplifier: usize, value: isize) {
+        self.state.lock().unwrap().queues[amplifier].push_back(value);
+        self.signal_ready.notify_all();
+    }
+
+    // None if the previous amplifier is gone for good or the whole loop got deadlocked
+    fn receive(&self, amplifier: usize) -> Option<isize> {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(value) = state.queues[amplifier].pop_front() {
+                return Some(value);
+            }
+            let previous = state.previous(amplifier);
+            if state.deadlocked || state.finished[previous] {
+                return None;
+            }
+
+            state.waiting[amplifier] = true;
+            if state.all_stuck() {
+                state.waiting[amplifier] = false;
+                state.deadlocked = true;
+                self.signal_ready.notify_all();
+                return None;
+            }
+            state = self.signal_ready.wait(state).unwrap();
+            state.waiting[amplifier] = false;
+        }
+    }
+
+    fn finish(&self, amplifier: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.finished[amplifier] = true;
+        drop(state);
+        self.signal_ready.notify_all();
+    }
+
+    fn is_deadlocked(&self) -> bool {
+        self.state.lock().unwrap().deadlocked
+    }
+}
+
+struct ChannelInput {
+    bus: Arc<SignalBus>,
+    amplifier: usize,
+}
+
+impl MachineInput for ChannelInput {
+    // blocks until the previous amplifier produces a signal; fails once it never will
+    fn read_value(&mut self) -> Option<isize> {
+        self.bus.receive(self.amplifier)
+    }
+}
+
+// the amplifier is done once its machine is gone, even if it panicked
+impl Drop for ChannelInput {
+    fn drop(&mut self) {
+        self.bus.finish(self.amplifier);
+    }
+}
+
+struct ChannelOutput {
+    bus: Arc<SignalBus>,
+    next: usize,
+    last_value: Option<isize>,
+}
+
+impl MachineOutput for ChannelOutput {
+    fn write_value(&mut self, value: isize) {
+        self.last_value = Some(value);
+        // the next amplifier might have already halted, in which case nobody cares about the value
+        self.bus.send(self.next, value);
+    }
+}
+
+type AmplifierResult = Result<Option<isize>, IntcodeMachineError>;
+
+// Runs every amplifier on its own thread, connecting output of each one to the input of the next
+// one (and the last back to the first). Once the last amplifier halts, its final output is
+// the resultant signal. If any amplifier fails, the ones after it run out of input and shut
+// down one after another. Amplifiers waiting on each other in a cycle result in a deadlock.
+pub fn run_feedback_loop(
+    tape: &Tape,
+    phases: &[usize],
+    initial_signal: isize,
+) -> Result<isize, TopologyError> {
+    assert!(!phases.is_empty());
+
+    let num_amplifiers = phases.len();
+    let bus = Arc::new(SignalBus::new(num_amplifiers));
+    for (i, &phase) in phases.iter().enumerate() {
+        bus.send(i, phase as isize);
+    }
+    bus.send(0, initial_signal);
+
+    let handles: Vec<_> = (0..num_amplifiers)
+        .map(|i| {
+            let input = ChannelInput {
+                bus: Arc::clone(&bus),
+                amplifier: i,
+            };
+            let output = ChannelOutput {
+                bus: Arc::clone(&bus),
+                next: (i + 1) % num_amplifiers,
+                last_value: None,
+            };
+            let tape = tape.clone();
+
+            thread::spawn(move || -> AmplifierResult {
+                let mut machine = IntcodeMachine::new(tape, input, output);
+                machine.run()?;
+                Ok(machine.into_output().last_value)
+            })
+        })
+        .collect();
+
+    // wait for every amplifier to shut down before reporting anything
+    let results: Vec<_> = handles.into_iter().map(|handle| handle.join()).collect();
+
+    let mut signal = None;
+    let mut starved = None;
+    for (i, result) in results.into_iter().enumerate() {
+        match result {
+            Ok(Ok(last_value)) => {
+                if i == phases.len() - 1 {
+                    signal = last_value;
+                }
+            }
+            // running out of input is usually just a consequence of another amplifier failing,
+            // so only report it if there is nothing better to report
+            Ok(Err(err @ IntcodeMachineError::InputFailure(_))) => {
+                if starved.is_none() {
+                    starved = Some(TopologyError::MachineFailure(i, err));
+                }
+            }
+            Ok(Err(err)) => return Err(TopologyError::MachineFailure(i, err)),
+            Err(_) => return Err(TopologyError::AmplifierPanicked(i)),
+        }
+    }
+
+    if bus.is_deadlocked() {
+        return Err(TopologyError::Deadlock);
+    }
+    if let Some(err) = starved {
+        return Err(err);
+    }
+    signal.ok_or(TopologyError::NoSignal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signals_passed_between_threads_produce_sample_result() {
+        let tape = Tape::new(vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ]);
+
+        assert_eq!(
+            139_629_729,
+            run_feedback_loop(&tape, &[9, 8, 7, 6, 5], 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn works_for_loops_of_arbitrary_size() {
+        // outputs `signal * 10 + phase` and halts
+        let tape = Tape::new(vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ]);
+
+        assert_eq!(123, run_feedback_loop(&tape, &[1, 2, 3], 0).unwrap());
+        assert_eq!(42, run_feedback_loop(&tape, &[2], 4).unwrap());
+    }
+
+    #[test]
+    fn failure_of_single_amplifier_shuts_down_the_loop() {
+        // amplifier with phase 1 hits an invalid opcode, all others just keep echoing
+        // their input: read a, read b, if a == 1 jump to invalid op, output b, loop on b
+        let tape = Tape::new(vec![
+            3, 20, 3, 21, 1008, 20, 1, 22, 1005, 22, 18, 4, 21, 3, 21, 1105, 1, 11, 98, 0, 0, 0, 0,
+        ]);
+
+        match run_feedback_loop(&tape, &[0, 0, 1, 0], 5) {
+            Err(TopologyError::MachineFailure(2, IntcodeMachineError::ExecutionFailure)) => (),
+            other => panic!("expected failure of the third amplifier, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn amplifiers_waiting_on_each_other_result_in_deadlock() {
+        // reads phase and two signals before writing anything
+        let tape = Tape::new(vec![3, 9, 3, 9, 3, 9, 4, 9, 99, 0]);
+
+        for phases in [&[0][..], &[0, 0], &[0, 0, 0, 0, 0]] {
+            match run_feedback_loop(&tape, phases, 5) {
+                Err(TopologyError::Deadlock) => (),
+                other => panic!("expected deadlock, got {:?}", other),
+            }
+        }
+    }
+}
@@ -1,20 +1,21 @@
+use std::collections::VecDeque;
 use std::convert::TryFrom;
 use std::io::{BufRead, Write};
 
 use crate::utils;
 
-const ADD_OP_CODE: isize = 1;
-const MUL_OP_CODE: isize = 2;
-const INPUT_OP_CODE: isize = 3;
-const OUTPUT_OP_CODE: isize = 4;
-const JMP_TRUE_OP_CODE: isize = 5;
-const JMP_FALSE_OP_CODE: isize = 6;
-const LESS_THAN_OP_CODE: isize = 7;
-const EQUALS_OP_CODE: isize = 8;
-const HALT_OP_CODE: isize = 99;
+pub const ADD_OP_CODE: isize = 1;
+pub const MUL_OP_CODE: isize = 2;
+pub const INPUT_OP_CODE: isize = 3;
+pub const OUTPUT_OP_CODE: isize = 4;
+pub const JMP_TRUE_OP_CODE: isize = 5;
+pub const JMP_FALSE_OP_CODE: isize = 6;
+pub const LESS_THAN_OP_CODE: isize = 7;
+pub const EQUALS_OP_CODE: isize = 8;
+pub const HALT_OP_CODE: isize = 99;
 
-const POSITION_MODE: usize = 0;
-const IMMEDIATE_MODE: usize = 1;
+pub const POSITION_MODE: usize = 0;
+pub const IMMEDIATE_MODE: usize = 1;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 enum ParamMode {
@@ -38,6 +39,73 @@ impl TryFrom<usize> for ParamMode {
 
 type HeadPositionUpdate = usize;
 
+pub trait MachineInput {
+    // None signals that no further input is (currently) available
+    fn read_value(&mut self) -> Option<isize>;
+}
+
+pub trait MachineOutput {
+    fn write_value(&mut self, value: isize);
+}
+
+// Unlike day9, failures are not told apart: running out of input, failing to read it and
+// reading something that is not a number all leave the machine without a value.
+impl<R: BufRead> MachineInput for R {
+    fn read_value(&mut self) -> Option<isize> {
+        let mut buffer = String::new();
+        match self.read_line(&mut buffer) {
+            Ok(0) | Err(_) => None,
+            Ok(_) => buffer.trim().parse::<isize>().ok(),
+        }
+    }
+}
+
+impl<W: Write> MachineOutput for W {
+    fn write_value(&mut self, value: isize) {
+        write!(self, "{}", value).unwrap();
+    }
+}
+
+// value-level I/O that does not have to go through text representation at all
+#[derive(Debug, Clone, Default)]
+pub struct ValueQueue(VecDeque<isize>);
+
+impl ValueQueue {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, value: isize) {
+        self.0.push_back(value)
+    }
+
+    pub fn pop(&mut self) -> Option<isize> {
+        self.0.pop_front()
+    }
+
+    pub fn drain(&mut self) -> Vec<isize> {
+        self.0.drain(..).collect()
+    }
+}
+
+impl From<Vec<isize>> for ValueQueue {
+    fn from(values: Vec<isize>) -> Self {
+        ValueQueue(values.into())
+    }
+}
+
+impl MachineInput for ValueQueue {
+    fn read_value(&mut self) -> Option<isize> {
+        self.pop()
+    }
+}
+
+impl MachineOutput for ValueQueue {
+    fn write_value(&mut self, value: isize) {
+        self.push(value)
+    }
+}
+
 #[derive(Debug)]
 enum OpCodeExecutionError {
     TapeError,
@@ -63,6 +131,8 @@ enum OpCode {
     Lt(Vec<ParamMode>),
     Eq(Vec<ParamMode>),
     Halt,
+    // the invalid code is only kept around for debugging purposes
+    #[allow(dead_code)]
     Er(isize),
 }
 
@@ -75,8 +145,8 @@ impl OpCode {
         writer: &mut W,
     ) -> Result<HeadPositionUpdate, OpCodeExecutionError>
     where
-        R: BufRead,
-        W: Write,
+        R: MachineInput,
+        W: MachineOutput,
     {
         use OpCode::*;
         match self {
@@ -220,18 +290,16 @@ impl OpCode {
         &self,
         tape: &mut Tape,
         head_position: usize,
-        mut reader: R,
+        reader: &mut R,
     ) -> Result<HeadPositionUpdate, OpCodeExecutionError>
     where
-        R: BufRead,
+        R: MachineInput,
     {
         let output_idx = tape.read(head_position + 1)?;
 
-        let mut buffer = String::new();
-        reader.read_line(&mut buffer).unwrap();
-        let input_value = match buffer.trim().parse::<isize>() {
-            Ok(val) => val,
-            _ => return Err(OpCodeExecutionError::InputFailure),
+        let input_value = match reader.read_value() {
+            Some(val) => val,
+            None => return Err(OpCodeExecutionError::InputFailure),
         };
 
         tape.write(output_idx as usize, input_value)?;
@@ -244,13 +312,13 @@ impl OpCode {
         tape: &mut Tape,
         head_position: usize,
         param_modes: Vec<ParamMode>,
-        mut writer: W,
+        writer: &mut W,
     ) -> Result<HeadPositionUpdate, OpCodeExecutionError>
     where
-        W: Write,
+        W: MachineOutput,
     {
         let output_val = self.mode_tape_read(tape, head_position + 1, param_modes[0])?;
-        write!(&mut writer, "{}", output_val).unwrap();
+        writer.write_value(output_val);
         Ok(head_position + 2)
     }
 }
@@ -267,7 +335,7 @@ impl From<isize> for OpCode {
         let digits = utils::num_to_digits_vec(code as usize);
 
         let mut opcode_digits: Vec<_> = std::iter::repeat(0)
-            .chain(digits.clone().into_iter())
+            .chain(digits.clone())
             .rev()
             .take(2)
             .collect();
@@ -288,7 +356,7 @@ impl From<isize> for OpCode {
         };
 
         let param_modes_vec: Vec<_> = std::iter::repeat(0)
-            .chain(digits.into_iter())
+            .chain(digits)
             .rev()
             .skip(2)
             .take(num_args)
@@ -320,16 +388,20 @@ enum TapeError {
 pub struct Tape(Vec<isize>);
 
 impl Tape {
-    pub(crate) fn new(input: Vec<isize>) -> Self {
+    pub fn new(input: Vec<isize>) -> Self {
         Tape(input)
     }
 
+    pub fn as_slice(&self) -> &[isize] {
+        &self.0
+    }
+
     fn len(&self) -> usize {
         self.0.len()
     }
 
     fn write(&mut self, position: usize, value: isize) -> Result<(), TapeError> {
-        if self.0.len() < position {
+        if position >= self.0.len() {
             return Err(TapeError::WriteOutOfRangeError);
         }
 
@@ -338,7 +410,7 @@ impl Tape {
     }
 
     fn read(&self, position: usize) -> Result<isize, TapeError> {
-        if self.0.len() < position {
+        if position >= self.0.len() {
             return Err(TapeError::ReadOutOfRangeError);
         }
 
@@ -378,6 +450,10 @@ impl State {
             head_position: 0,
         }
     }
+
+    pub fn tape(&self) -> &Tape {
+        &self.tape
+    }
 }
 
 impl Default for State {
@@ -391,8 +467,8 @@ impl Default for State {
 
 pub struct IntcodeMachine<R, W>
 where
-    R: BufRead,
-    W: Write,
+    R: MachineInput,
+    W: MachineOutput,
 {
     tape: Tape,
     head_position: usize,
@@ -402,10 +478,10 @@ where
 
 impl<R, W> IntcodeMachine<R, W>
 where
-    R: BufRead,
-    W: Write,
+    R: MachineInput,
+    W: MachineOutput,
 {
-    pub(crate) fn new(tape: Tape, reader: R, writer: W) -> Self {
+    pub fn new(tape: Tape, reader: R, writer: W) -> Self {
         IntcodeMachine {
             tape,
             head_position: 0,
@@ -423,6 +499,10 @@ where
         }
     }
 
+    pub fn into_output(self) -> W {
+        self.output
+    }
+
     pub fn dump_state(&self) -> State {
         State {
             tape: self.tape.clone(),
@@ -440,30 +520,38 @@ where
         Ok(())
     }
 
-    pub(crate) fn run(&mut self) -> Result<isize, IntcodeMachineError> {
+    // executes a single instruction, returns the value at position 0 once the machine halts
+    pub fn step(&mut self) -> Result<Option<isize>, IntcodeMachineError> {
+        let op = OpCode::from(self.tape.read(self.head_position)?);
+        let head_update = match op.execute(
+            &mut self.tape,
+            self.head_position,
+            &mut self.input,
+            &mut self.output,
+        ) {
+            Err(err) => match err {
+                OpCodeExecutionError::ExecutionFinished => {
+                    return Ok(Some(self.tape.read(0)?));
+                }
+                OpCodeExecutionError::InputFailure => {
+                    return Err(IntcodeMachineError::InputFailure(self.dump_state()))
+                }
+                _ => {
+                    return Err(IntcodeMachineError::ExecutionFailure);
+                }
+            },
+            Ok(head_update) => head_update,
+        };
+
+        self.update_head(head_update)?;
+        Ok(None)
+    }
+
+    pub fn run(&mut self) -> Result<isize, IntcodeMachineError> {
         loop {
-            let op = OpCode::from(self.tape.read(self.head_position)?);
-            let head_update = match op.execute(
-                &mut self.tape,
-                self.head_position,
-                &mut self.input,
-                &mut self.output,
-            ) {
-                Err(err) => match err {
-                    OpCodeExecutionError::ExecutionFinished => {
-                        return Ok(self.tape.read(0)?);
-                    }
-                    OpCodeExecutionError::InputFailure => {
-                        return Err(IntcodeMachineError::InputFailure(self.dump_state()))
-                    }
-                    _ => {
-                        return Err(IntcodeMachineError::ExecutionFailure);
-                    }
-                },
-                Ok(head_update) => head_update,
-            };
-
-            self.update_head(head_update)?;
+            if let Some(output) = self.step()? {
+                return Ok(output);
+            }
         }
     }
 }
@@ -501,4 +589,37 @@ mod tests {
         let output = String::from_utf8(output).unwrap().parse::<isize>().unwrap();
         assert_eq!(584_126, output);
     }
+
+    #[test]
+    fn accessing_cell_right_past_the_tape_is_an_error() {
+        // used to be let through by an off-by-one in the range check and panic instead
+        for tape in [vec![4, 3, 99], vec![1101, 1, 1, 4]].iter() {
+            let mut machine = IntcodeMachine::new(
+                Tape::new(tape.clone()),
+                ValueQueue::new(),
+                ValueQueue::new(),
+            );
+            match machine.run() {
+                Err(IntcodeMachineError::ExecutionFailure) => (),
+                other => panic!("expected execution failure, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn failing_reader_is_reported_as_missing_input() {
+        struct Unplugged;
+
+        impl std::io::Read for Unplugged {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("device unplugged"))
+            }
+        }
+
+        let reader = std::io::BufReader::new(Unplugged);
+        match IntcodeMachine::new(Tape::new(vec![3, 0, 99]), reader, Vec::new()).run() {
+            Err(IntcodeMachineError::InputFailure(_)) => (),
+            other => panic!("expected input failure, got {:?}", other),
+        }
+    }
 }
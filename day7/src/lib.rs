@@ -0,0 +1,6 @@
+pub mod amplifier;
+pub mod feedback;
+pub mod intcode_machine;
+pub mod phase_search;
+pub mod topology;
+pub mod utils;
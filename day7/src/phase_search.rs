@@ -0,0 +1,180 @@
+use std::sync::Mutex;
+use std::thread;
+
+use permutohedron::LexicalPermutation;
+
+use crate::amplifier::{AmplifierMode, AmplifierPhaseSequence};
+use crate::intcode_machine::Tape;
+use crate::topology::TopologyError;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchResult {
+    pub sequence: AmplifierPhaseSequence,
+    pub signal: isize,
+}
+
+impl SearchResult {
+    // ties are resolved in favour of the lexicographically smaller sequence so that the result
+    // does not depend on how the candidates got scheduled
+    fn is_better_than(&self, other: &SearchResult) -> bool {
+        self.signal > other.signal
+            || (self.signal == other.signal && self.sequence.phases() < other.sequence.phases())
+    }
+}
+
+struct SharedSearch {
+    // next permutation to hand out, None once all of them were distributed
+    next_candidate: Mutex<Option<Vec<usize>>>,
+    best: Mutex<Option<SearchResult>>,
+    failure: Mutex<Option<TopologyError>>,
+}
+
+impl SharedSearch {
+    fn take_candidate(&self) -> Option<Vec<usize>> {
+        let mut next_candidate = self.next_candidate.lock().unwrap();
+        let current = next_candidate.as_mut()?;
+        let candidate = current.clone();
+        if !current.next_permutation() {
+            *next_candidate = None;
+        }
+        Some(candidate)
+    }
+
+    fn submit(&self, result: SearchResult) {
+        let mut best = self.best.lock().unwrap();
+        let is_improvement = match best.as_ref() {
+            Some(best) => result.is_better_than(best),
+            None => true,
+        };
+        if is_improvement {
+            *best = Some(result);
+        }
+    }
+
+    fn fail(&self, err: TopologyError) {
+        // stop handing out any more work
+        *self.next_candidate.lock().unwrap() = None;
+
+        let mut failure = self.failure.lock().unwrap();
+        if failure.is_none() {
+            *failure = Some(err);
+        }
+    }
+
+    fn work(&self, tape: &Tape, mode: AmplifierMode) {
+        while let Some(candidate) = self.take_candidate() {
+            let sequence = AmplifierPhaseSequence::new(candidate);
+            match sequence.run(tape.clone(), mode) {
+                Ok(signal) => self.submit(SearchResult { sequence, signal }),
+                Err(err) => self.fail(err),
+            }
+        }
+    }
+}
+
+pub fn parallel_search_with_workers(
+    tape: &Tape,
+    phases: &[usize],
+    mode: AmplifierMode,
+    num_workers: usize,
+) -> Result<SearchResult, TopologyError> {
+    assert!(!phases.is_empty(), "there must be at least a single phase");
+    assert!(num_workers > 0, "there must be at least a single worker");
+
+    // permutations are generated in lexical order, so we have to start with the smallest one
+    let mut first_candidate = phases.to_vec();
+    first_candidate.sort();
+
+    let search = SharedSearch {
+        next_candidate: Mutex::new(Some(first_candidate)),
+        best: Mutex::new(None),
+        failure: Mutex::new(None),
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..num_workers {
+            scope.spawn(|| search.work(tape, mode));
+        }
+    });
+
+    if let Some(err) = search.failure.into_inner().unwrap() {
+        return Err(err);
+    }
+
+    // there always is at least a single candidate and if it did not fail it must have submitted
+    Ok(search.best.into_inner().unwrap().unwrap())
+}
+
+pub fn parallel_search(
+    tape: &Tape,
+    phases: &[usize],
+    mode: AmplifierMode,
+) -> Result<SearchResult, TopologyError> {
+    let num_workers = thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    parallel_search_with_workers(tape, phases, mode, num_workers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_best_series_sequence_for_first_sample_input() {
+        let tape = Tape::new(vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ]);
+
+        let result = parallel_search(&tape, &[0, 1, 2, 3, 4], AmplifierMode::Series).unwrap();
+        assert_eq!(43210, result.signal);
+        assert_eq!(&[4, 3, 2, 1, 0], result.sequence.phases());
+    }
+
+    #[test]
+    fn finds_best_feedback_sequence_for_sample_input() {
+        let tape = Tape::new(vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ]);
+
+        let result = parallel_search(&tape, &[5, 6, 7, 8, 9], AmplifierMode::Feedback).unwrap();
+        assert_eq!(139_629_729, result.signal);
+        assert_eq!(&[9, 8, 7, 6, 5], result.sequence.phases());
+    }
+
+    #[test]
+    fn result_does_not_depend_on_number_of_workers() {
+        let tape = Tape::new(vec![
+            3, 23, 3, 24, 1002, 24, 10, 24, 1002, 23, -1, 23, 101, 5, 23, 23, 1, 24, 23, 23, 4, 23,
+            99, 0, 0,
+        ]);
+
+        let single =
+            parallel_search_with_workers(&tape, &[0, 1, 2, 3, 4], AmplifierMode::Series, 1);
+        let many = parallel_search_with_workers(&tape, &[0, 1, 2, 3, 4], AmplifierMode::Series, 8);
+        assert_eq!(single.unwrap(), many.unwrap());
+    }
+
+    #[test]
+    fn supports_arbitrary_phase_sets() {
+        // outputs `signal * 10 + phase`, so the best sequence is the descending one
+        let tape = Tape::new(vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ]);
+
+        let result = parallel_search(&tape, &[7, 1, 3], AmplifierMode::Series).unwrap();
+        assert_eq!(731, result.signal);
+        assert_eq!(&[7, 3, 1], result.sequence.phases());
+    }
+
+    #[test]
+    fn machine_failure_is_propagated() {
+        let tape = Tape::new(vec![3, 0, 98]);
+
+        match parallel_search(&tape, &[0, 1, 2], AmplifierMode::Series) {
+            Err(TopologyError::MachineFailure(0, _)) => (),
+            other => panic!("expected machine failure, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::VecDeque;
+
+use crate::intcode_machine::{IntcodeMachine, IntcodeMachineError, State, Tape, ValueQueue};
+
+#[derive(Debug)]
+pub enum TopologyError {
+    MachineFailure(usize, IntcodeMachineError),
+    AmplifierPanicked(usize),
+    Deadlock,
+    NoSignal,
+}
+
+struct Node {
+    // None once the machine has halted
+    state: Option<State>,
+    inputs: VecDeque<isize>,
+    successors: Vec<usize>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Topology {
+    phases: Vec<usize>,
+    edges: Vec<(usize, usize)>,
+    entry: usize,
+    exit: usize,
+}
+
+impl Topology {
+    pub fn new(phases: Vec<usize>, edges: Vec<(usize, usize)>, entry: usize, exit: usize) -> Self {
+        let num_nodes = phases.len();
+        assert!(num_nodes > 0, "topology needs at least a single node");
+        assert!(entry < num_nodes && exit < num_nodes);
+        assert!(edges
+            .iter()
+            .all(|&(from, to)| from < num_nodes && to < num_nodes));
+
+        Topology {
+            phases,
+            edges,
+            entry,
+            exit,
+        }
+    }
+
+    // A -> B -> ... -> Z
+    pub fn chain(phases: Vec<usize>) -> Self {
+        let num_nodes = phases.len();
+        let edges = (1..num_nodes).map(|i| (i - 1, i)).collect();
+        Self::new(phases, edges, 0, num_nodes - 1)
+    }
+
+    // A -> B -> ... -> Z -> A
+    pub fn ring(phases: Vec<usize>) -> Self {
+        let num_nodes = phases.len();
+        let edges = (0..num_nodes).map(|i| (i, (i + 1) % num_nodes)).collect();
+        Self::new(phases, edges, 0, num_nodes - 1)
+    }
+
+    pub fn phases(&self) -> &[usize] {
+        &self.phases
+    }
+
+    fn build_nodes(&self, tape: Tape, initial_signal: isize) -> Vec<Node> {
+        let mut nodes: Vec<_> = self
+            .phases
+            .iter()
+            .map(|&phase| Node {
+                state: Some(State::new_from_tape(tape.clone())),
+                inputs: vec![phase as isize].into(),
+                successors: Vec::new(),
+            })
+            .collect();
+
+        for &(from, to) in &self.edges {
+            nodes[from].successors.push(to);
+        }
+        nodes[self.entry].inputs.push_back(initial_signal);
+
+        nodes
+    }
+
+    // Every machine is resumed in turn with all of the signals queued for it and whatever it
+    // outputs is broadcast to all of its successors. Execution finishes once the exit node halts
+    // and the final signal is the last value it has emitted.
+    pub fn run(&self, tape: Tape, initial_signal: isize) -> Result<isize, TopologyError> {
+        let mut nodes = self.build_nodes(tape, initial_signal);
+        let mut final_signal = None;
+
+        while nodes[self.exit].state.is_some() {
+            let mut made_progress = false;
+
+            for i in 0..nodes.len() {
+                if nodes[i].state.is_none() || nodes[i].inputs.is_empty() {
+                    continue;
+                }
+                made_progress = true;
+
+                let input = ValueQueue::from(nodes[i].inputs.drain(..).collect::<Vec<_>>());
+                let state = nodes[i].state.take().unwrap();
+                let mut machine = IntcodeMachine::load_state(state, input, ValueQueue::new());
+                nodes[i].state = match machine.run() {
+                    Ok(_) => None,
+                    Err(IntcodeMachineError::InputFailure(state)) => Some(state),
+                    Err(err) => return Err(TopologyError::MachineFailure(i, err)),
+                };
+                let outputs = machine.into_output().drain();
+
+                if i == self.exit {
+                    if let Some(&last) = outputs.last() {
+                        final_signal = Some(last);
+                    }
+                }
+
+                for successor in nodes[i].successors.clone() {
+                    nodes[successor].inputs.extend(&outputs);
+                }
+            }
+
+            if !made_progress {
+                return Err(TopologyError::Deadlock);
+            }
+        }
+
+        final_signal.ok_or(TopologyError::NoSignal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // takes phase and signal and outputs `signal * 10 + phase`
+    fn shift_tape() -> Tape {
+        Tape::new(vec![
+            3, 15, 3, 16, 1002, 16, 10, 16, 1, 16, 15, 15, 4, 15, 99, 0, 0,
+        ])
+    }
+
+    #[test]
+    fn chain_of_arbitrary_length_passes_signal_through() {
+        let topology = Topology::chain(vec![1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(1_234_567, topology.run(shift_tape(), 0).unwrap());
+    }
+
+    #[test]
+    fn single_node_chain_works() {
+        let topology = Topology::chain(vec![3]);
+        assert_eq!(23, topology.run(shift_tape(), 2).unwrap());
+    }
+
+    #[test]
+    fn ring_of_five_matches_feedback_example() {
+        let tape = Tape::new(vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ]);
+        let topology = Topology::ring(vec![9, 8, 7, 6, 5]);
+
+        assert_eq!(139_629_729, topology.run(tape, 0).unwrap());
+    }
+
+    // reads phase `n`, sums the next `n` signals and outputs that sum increased by `n`
+    fn summing_tape() -> Tape {
+        Tape::new(vec![
+            3, 26, 1001, 26, 0, 28, 3, 29, 1, 27, 29, 27, 1001, 28, -1, 28, 1005, 28, 6, 1, 27, 26,
+            27, 4, 27, 99, 0, 0, 0, 0,
+        ])
+    }
+
+    #[test]
+    fn fan_out_and_fan_in_graph_combines_signals() {
+        // 0 -> {1, 2} -> 3
+        let topology = Topology::new(vec![1, 1, 1, 2], vec![(0, 1), (0, 2), (1, 3), (2, 3)], 0, 3);
+
+        assert_eq!(26, topology.run(summing_tape(), 10).unwrap());
+    }
+
+    // reads phase `n` and outputs `-n`, then sums the next `n` signals and outputs the sum
+    fn negating_summing_tape() -> Tape {
+        let mut program = vec![
+            3, 40, 1002, 40, -1, 43, 4, 43, 1001, 40, 0, 41, 1006, 41, 28, 3, 43, 1, 42, 43, 42,
+            1001, 41, -1, 41, 1105, 1, 12, 4, 42, 99,
+        ];
+        program.resize(44, 0);
+        Tape::new(program)
+    }
+
+    #[test]
+    fn every_emitted_value_is_passed_on_separately() {
+        // single node emits -1 and 5, only the last of them is the final signal
+        let topology = Topology::chain(vec![1]);
+        assert_eq!(5, topology.run(negating_summing_tape(), 5).unwrap());
+
+        // second node receives both -1 and 5 and outputs -2 followed by their sum
+        let topology = Topology::chain(vec![1, 2]);
+        assert_eq!(4, topology.run(negating_summing_tape(), 5).unwrap());
+    }
+
+    #[test]
+    fn starved_node_results_in_deadlock() {
+        let topology = Topology::new(vec![1, 1, 3], vec![(0, 2), (1, 2)], 0, 2);
+
+        match topology.run(summing_tape(), 10) {
+            Err(TopologyError::Deadlock) => (),
+            other => panic!("expected deadlock, got {:?}", other),
+        }
+    }
+}
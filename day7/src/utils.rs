@@ -1,5 +1,4 @@
 use itertools::Itertools;
-use std::fs;
 
 pub fn num_to_digits_vec(val: usize) -> Vec<usize> {
     let mut digits = Vec::new();
@@ -13,7 +12,7 @@ pub fn num_to_digits_vec(val: usize) -> Vec<usize> {
     digits
 }
 
-pub fn digits_vec_to_num(digits: &Vec<usize>) -> usize {
+pub fn digits_vec_to_num(digits: &[usize]) -> usize {
     digits
         .iter()
         .cloned()
@@ -25,9 +24,8 @@ pub fn digits_vec_to_num(digits: &Vec<usize>) -> usize {
 }
 
 pub fn read_input_file(path: &str) -> Vec<isize> {
-    fs::read_to_string(path)
-        .unwrap()
-        .split(',')
-        .map(|s| s.parse::<isize>().unwrap())
-        .collect()
+    match intcode_loader::load_program(path) {
+        Ok(program) => program,
+        Err(err) => panic!("could not load {}: {}", path, err),
+    }
 }
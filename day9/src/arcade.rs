@@ -0,0 +1,290 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io::{self, Write};
+
+use crate::intcode_machine::{IntcodeMachine, IntcodeMachineError, StepOutcome, Tape, ValueQueue};
+
+const FREE_PLAY_ADDRESS: usize = 0;
+const FREE_PLAY_QUARTERS: isize = 2;
+
+// output triple at this position updates the score instead of drawing a tile
+const SCORE_POSITION: Position = (-1, 0);
+
+const EMPTY_TILE: isize = 0;
+const WALL_TILE: isize = 1;
+const BLOCK_TILE: isize = 2;
+const PADDLE_TILE: isize = 3;
+const BALL_TILE: isize = 4;
+
+const ANSI_CLEAR_SCREEN: &str = "\x1b[2J\x1b[H";
+
+pub type Position = (isize, isize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tile {
+    Empty,
+    Wall,
+    Block,
+    Paddle,
+    Ball,
+}
+
+impl TryFrom<isize> for Tile {
+    type Error = ArcadeError;
+
+    fn try_from(value: isize) -> Result<Self, Self::Error> {
+        use Tile::*;
+
+        match value {
+            EMPTY_TILE => Ok(Empty),
+            WALL_TILE => Ok(Wall),
+            BLOCK_TILE => Ok(Block),
+            PADDLE_TILE => Ok(Paddle),
+            BALL_TILE => Ok(Ball),
+            _ => Err(ArcadeError::InvalidTile(value)),
+        }
+    }
+}
+
+impl Tile {
+    fn symbol(self) -> char {
+        use Tile::*;
+
+        match self {
+            Empty => ' ',
+            Wall => '#',
+            Block => '=',
+            Paddle => '-',
+            Ball => 'o',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Joystick {
+    Left,
+    Neutral,
+    Right,
+}
+
+impl From<Joystick> for isize {
+    fn from(joystick: Joystick) -> Self {
+        match joystick {
+            Joystick::Left => -1,
+            Joystick::Neutral => 0,
+            Joystick::Right => 1,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ArcadeError {
+    MachineFailure(IntcodeMachineError),
+    InvalidTile(isize),
+    RenderFailure(io::Error),
+}
+
+impl From<IntcodeMachineError> for ArcadeError {
+    fn from(err: IntcodeMachineError) -> Self {
+        ArcadeError::MachineFailure(err)
+    }
+}
+
+impl From<io::Error> for ArcadeError {
+    fn from(err: io::Error) -> Self {
+        ArcadeError::RenderFailure(err)
+    }
+}
+
+pub struct Arcade {
+    machine: IntcodeMachine<ValueQueue, ValueQueue>,
+    screen: HashMap<Position, Tile>,
+    score: isize,
+    ball: Option<Position>,
+    paddle: Option<Position>,
+}
+
+impl Arcade {
+    pub fn new(mut tape: Tape, free_play: bool) -> Self {
+        if free_play {
+            tape.write(FREE_PLAY_ADDRESS, FREE_PLAY_QUARTERS);
+        }
+
+        Arcade {
+            machine: IntcodeMachine::new(tape, ValueQueue::new(), ValueQueue::new()),
+            screen: HashMap::new(),
+            score: 0,
+            ball: None,
+            paddle: None,
+        }
+    }
+
+    pub fn score(&self) -> isize {
+        self.score
+    }
+
+    pub fn tile(&self, position: Position) -> Tile {
+        *self.screen.get(&position).unwrap_or(&Tile::Empty)
+    }
+
+    pub fn count_tiles(&self, tile: Tile) -> usize {
+        self.screen.values().filter(|&&t| t == tile).count()
+    }
+
+    fn draw(&mut self, x: isize, y: isize, value: isize) -> Result<(), ArcadeError> {
+        if (x, y) == SCORE_POSITION {
+            self.score = value;
+            return Ok(());
+        }
+
+        let tile = Tile::try_from(value)?;
+        match tile {
+            Tile::Ball => self.ball = Some((x, y)),
+            Tile::Paddle => self.paddle = Some((x, y)),
+            _ => (),
+        }
+        self.screen.insert((x, y), tile);
+
+        Ok(())
+    }
+
+    // simply keeps the paddle right below the ball
+    pub fn joystick(&self) -> Joystick {
+        match (self.ball, self.paddle) {
+            (Some((ball_x, _)), Some((paddle_x, _))) => match ball_x.cmp(&paddle_x) {
+                Ordering::Less => Joystick::Left,
+                Ordering::Equal => Joystick::Neutral,
+                Ordering::Greater => Joystick::Right,
+            },
+            _ => Joystick::Neutral,
+        }
+    }
+
+    fn run_with<F>(&mut self, mut on_frame: F) -> Result<isize, ArcadeError>
+    where
+        F: FnMut(&Arcade) -> Result<(), ArcadeError>,
+    {
+        loop {
+            match self.machine.step()? {
+                StepOutcome::Executed => {
+                    if self.machine.output_mut().len() == 3 {
+                        let output = self.machine.output_mut().drain();
+                        self.draw(output[0], output[1], output[2])?;
+                    }
+                }
+                // game only ever asks for input once the whole frame got drawn
+                StepOutcome::AwaitingInput => {
+                    on_frame(self)?;
+                    let joystick = self.joystick().into();
+                    self.machine.input_mut().push(joystick);
+                }
+                StepOutcome::Halted => {
+                    on_frame(self)?;
+                    return Ok(self.score);
+                }
+            }
+        }
+    }
+
+    // plays the game without displaying anything and returns the final score
+    pub fn play(&mut self) -> Result<isize, ArcadeError> {
+        self.run_with(|_| Ok(()))
+    }
+
+    pub fn play_rendered<W: Write>(&mut self, mut out: W) -> Result<isize, ArcadeError> {
+        self.run_with(|arcade| {
+            write!(out, "{}{}", ANSI_CLEAR_SCREEN, arcade.render())?;
+            writeln!(out, "Score: {}", arcade.score())?;
+            out.flush()?;
+            Ok(())
+        })
+    }
+
+    pub fn render(&self) -> String {
+        let max_x = self.screen.keys().map(|&(x, _)| x).max().unwrap_or(-1);
+        let max_y = self.screen.keys().map(|&(_, y)| y).max().unwrap_or(-1);
+
+        let mut rendered = String::new();
+        for y in 0..=max_y {
+            for x in 0..=max_x {
+                rendered.push(self.tile((x, y)).symbol());
+            }
+            rendered.push('\n');
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // draws a tiny screen, reads the joystick once, sets score to joystick + 10,
+    // destroys one of the blocks and halts
+    fn tiny_game_tape() -> Tape {
+        Tape::new(vec![
+            104, 0, 104, 0, 104, 1, 104, 1, 104, 0, 104, 2, 104, 2, 104, 0, 104, 2, 104, 3, 104, 1,
+            104, 3, 104, 5, 104, 2, 104, 4, 104, -1, 104, 0, 104, 1234, 3, 60, 1001, 60, 10, 61,
+            104, -1, 104, 0, 4, 61, 104, 2, 104, 0, 104, 0, 99,
+        ])
+    }
+
+    #[test]
+    fn screen_is_drawn_from_output_triples() {
+        let mut arcade = Arcade::new(tiny_game_tape(), false);
+        arcade.play().unwrap();
+
+        assert_eq!(Tile::Wall, arcade.tile((0, 0)));
+        assert_eq!(Tile::Block, arcade.tile((1, 0)));
+        assert_eq!(Tile::Empty, arcade.tile((2, 0)));
+        assert_eq!(Tile::Paddle, arcade.tile((3, 1)));
+        assert_eq!(Tile::Ball, arcade.tile((5, 2)));
+        assert_eq!(1, arcade.count_tiles(Tile::Block));
+    }
+
+    #[test]
+    fn autoplayer_moves_paddle_towards_the_ball() {
+        // ball is to the right of the paddle, so joystick should be tilted right (1)
+        let mut arcade = Arcade::new(tiny_game_tape(), false);
+        assert_eq!(11, arcade.play().unwrap());
+    }
+
+    #[test]
+    fn free_play_is_written_into_first_memory_cell() {
+        // first instruction either adds or multiplies the contents of cell 0 with itself
+        let tape = Tape::new(vec![1, 0, 0, 20, 104, -1, 104, 0, 4, 20, 99]);
+
+        assert_eq!(2, Arcade::new(tape.clone(), false).play().unwrap());
+        assert_eq!(4, Arcade::new(tape, true).play().unwrap());
+    }
+
+    #[test]
+    fn screen_is_rendered_as_text() {
+        let mut arcade = Arcade::new(tiny_game_tape(), false);
+        arcade.play().unwrap();
+
+        assert_eq!("#=    \n   -  \n     o\n", arcade.render());
+    }
+
+    #[test]
+    fn ansi_renderer_draws_every_frame() {
+        let mut arcade = Arcade::new(tiny_game_tape(), false);
+        let mut out = Vec::new();
+        arcade.play_rendered(&mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(2, out.matches(ANSI_CLEAR_SCREEN).count());
+        assert!(out.contains("Score: 1234\n"));
+        assert!(out.ends_with("Score: 11\n"));
+    }
+
+    #[test]
+    fn invalid_tiles_are_rejected() {
+        let mut arcade = Arcade::new(Tape::new(vec![104, 1, 104, 1, 104, 7, 99]), false);
+        match arcade.play() {
+            Err(ArcadeError::InvalidTile(7)) => (),
+            other => panic!("expected invalid tile, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,185 @@
+use crate::intcode_machine::{
+    IntcodeMachine, IntcodeMachineError, State, StepOutcome, Tape, ValueQueue,
+};
+
+const ASCII_LIMIT: isize = 128;
+const NEWLINE: isize = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AsciiOutput {
+    Text(String),
+    Value(isize),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum AsciiError {
+    NonAsciiCharacter(char),
+}
+
+// only ASCII text can be fed to the machine
+pub fn encode(text: &str) -> Result<Vec<isize>, AsciiError> {
+    text.chars()
+        .map(|c| {
+            if c.is_ascii() {
+                Ok(c as isize)
+            } else {
+                Err(AsciiError::NonAsciiCharacter(c))
+            }
+        })
+        .collect()
+}
+
+// groups consecutive ASCII characters into text while keeping anything else as a raw value
+pub fn decode(values: &[isize]) -> Vec<AsciiOutput> {
+    let mut decoded = Vec::new();
+    let mut text = String::new();
+
+    for &value in values {
+        if (0..ASCII_LIMIT).contains(&value) {
+            text.push(value as u8 as char);
+        } else {
+            if !text.is_empty() {
+                decoded.push(AsciiOutput::Text(std::mem::take(&mut text)));
+            }
+            decoded.push(AsciiOutput::Value(value));
+        }
+    }
+
+    if !text.is_empty() {
+        decoded.push(AsciiOutput::Text(text));
+    }
+    decoded
+}
+
+pub struct AsciiMachine {
+    machine: IntcodeMachine<ValueQueue, ValueQueue>,
+}
+
+impl AsciiMachine {
+    pub fn new(tape: Tape) -> Self {
+        AsciiMachine {
+            machine: IntcodeMachine::new(tape, ValueQueue::new(), ValueQueue::new()),
+        }
+    }
+
+    // any pending input or output is not part of the state and is lost
+    pub fn load_state(state: State) -> Self {
+        AsciiMachine {
+            machine: IntcodeMachine::load_state(state, ValueQueue::new(), ValueQueue::new()),
+        }
+    }
+
+    pub fn dump_state(&self) -> State {
+        self.machine.dump_state()
+    }
+
+    // nothing is sent unless the whole text can be encoded
+    pub fn send_str(&mut self, text: &str) -> Result<(), AsciiError> {
+        for value in encode(text)? {
+            self.machine.input_mut().push(value);
+        }
+        Ok(())
+    }
+
+    pub fn send_line(&mut self, line: &str) -> Result<(), AsciiError> {
+        self.send_str(line)?;
+        self.machine.input_mut().push(NEWLINE);
+        Ok(())
+    }
+
+    // runs until the machine either halts or requires more input than it was given.
+    // Returned outcome is never `StepOutcome::Executed`.
+    pub fn run(&mut self) -> Result<StepOutcome, IntcodeMachineError> {
+        loop {
+            match self.machine.step()? {
+                StepOutcome::Executed => (),
+                outcome => return Ok(outcome),
+            }
+        }
+    }
+
+    pub fn take_output(&mut self) -> Vec<AsciiOutput> {
+        decode(&self.machine.output_mut().drain())
+    }
+
+    pub fn take_text(&mut self) -> String {
+        self.take_output()
+            .into_iter()
+            .map(|output| match output {
+                AsciiOutput::Text(text) => text,
+                AsciiOutput::Value(value) => format!("{}\n", value),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // echoes its input back until it reads a newline, then outputs 1000 and halts
+    fn echo_tape() -> Tape {
+        Tape::new(vec![
+            3, 100, 4, 100, 1008, 100, 10, 101, 1006, 101, 0, 104, 1000, 99,
+        ])
+    }
+
+    #[test]
+    fn decoding_groups_text_and_keeps_large_values() {
+        assert_eq!(
+            vec![
+                AsciiOutput::Text("Hi\n".to_string()),
+                AsciiOutput::Value(200),
+                AsciiOutput::Value(-1),
+                AsciiOutput::Text("A".to_string()),
+            ],
+            decode(&[72, 105, 10, 200, -1, 65])
+        );
+    }
+
+    #[test]
+    fn encoding_produces_character_codes() {
+        assert_eq!(Ok(vec![78, 79, 84, 32, 65]), encode("NOT A"));
+    }
+
+    #[test]
+    fn non_ascii_text_is_rejected_without_feeding_anything() {
+        assert_eq!(Err(AsciiError::NonAsciiCharacter('é')), encode("café"));
+
+        let mut machine = AsciiMachine::new(echo_tape());
+        assert_eq!(
+            Err(AsciiError::NonAsciiCharacter('→')),
+            machine.send_line("go → north")
+        );
+        assert_eq!(StepOutcome::AwaitingInput, machine.run().unwrap());
+        assert_eq!("", machine.take_text());
+    }
+
+    #[test]
+    fn line_is_fed_as_character_codes_and_echoed_back_as_text() {
+        let mut machine = AsciiMachine::new(echo_tape());
+        machine.send_line("hello").unwrap();
+
+        assert_eq!(StepOutcome::Halted, machine.run().unwrap());
+        assert_eq!(
+            vec![
+                AsciiOutput::Text("hello\n".to_string()),
+                AsciiOutput::Value(1000)
+            ],
+            machine.take_output()
+        );
+    }
+
+    #[test]
+    fn machine_waits_for_more_input_when_line_is_incomplete() {
+        let mut machine = AsciiMachine::new(echo_tape());
+        machine.send_str("ab").unwrap();
+
+        assert_eq!(StepOutcome::AwaitingInput, machine.run().unwrap());
+        assert_eq!("ab", machine.take_text());
+
+        machine.send_line("c").unwrap();
+        assert_eq!(StepOutcome::Halted, machine.run().unwrap());
+        assert_eq!("c\n1000\n", machine.take_text());
+    }
+}
@@ -0,0 +1,413 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::{poll_fn, Future};
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+use crate::intcode_machine::{
+    IntcodeMachine, IntcodeMachineError, State, StepOutcome, Tape, ValueQueue,
+};
+
+// number of instructions a machine executes before giving other tasks a chance to run
+const STEPS_BEFORE_YIELD: usize = 1000;
+
+pub trait AsyncSource {
+    // Ready(None) means the source got closed and no more values are ever going to arrive
+    fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Option<isize>>;
+}
+
+pub trait AsyncSink {
+    fn poll_write(&mut self, cx: &mut Context<'_>, value: isize) -> Poll<()>;
+}
+
+struct ChannelState {
+    queue: VecDeque<isize>,
+    receiver_waker: Option<Waker>,
+    senders: usize,
+}
+
+impl ChannelState {
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.receiver_waker.take() {
+            waker.wake()
+        }
+    }
+}
+
+// unbounded single-threaded channel, so sending never has to wait
+pub struct AsyncSender(Rc<RefCell<ChannelState>>);
+
+pub struct AsyncReceiver(Rc<RefCell<ChannelState>>);
+
+pub fn channel() -> (AsyncSender, AsyncReceiver) {
+    let state = Rc::new(RefCell::new(ChannelState {
+        queue: VecDeque::new(),
+        receiver_waker: None,
+        senders: 1,
+    }));
+
+    (AsyncSender(Rc::clone(&state)), AsyncReceiver(state))
+}
+
+impl AsyncSender {
+    pub fn send(&self, value: isize) {
+        let mut state = self.0.borrow_mut();
+        state.queue.push_back(value);
+        state.wake_receiver();
+    }
+}
+
+impl Clone for AsyncSender {
+    fn clone(&self) -> Self {
+        self.0.borrow_mut().senders += 1;
+        AsyncSender(Rc::clone(&self.0))
+    }
+}
+
+impl Drop for AsyncSender {
+    fn drop(&mut self) {
+        let mut state = self.0.borrow_mut();
+        state.senders -= 1;
+        if state.senders == 0 {
+            state.wake_receiver();
+        }
+    }
+}
+
+impl AsyncSink for AsyncSender {
+    fn poll_write(&mut self, _: &mut Context<'_>, value: isize) -> Poll<()> {
+        self.send(value);
+        Poll::Ready(())
+    }
+}
+
+impl AsyncReceiver {
+    pub fn try_recv(&self) -> Option<isize> {
+        self.0.borrow_mut().queue.pop_front()
+    }
+}
+
+impl AsyncSource for AsyncReceiver {
+    fn poll_read(&mut self, cx: &mut Context<'_>) -> Poll<Option<isize>> {
+        let mut state = self.0.borrow_mut();
+        match state.queue.pop_front() {
+            Some(value) => Poll::Ready(Some(value)),
+            None if state.senders == 0 => Poll::Ready(None),
+            None => {
+                state.receiver_waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+async fn yield_now() {
+    let mut yielded = false;
+    poll_fn(|cx| {
+        if yielded {
+            Poll::Ready(())
+        } else {
+            yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    })
+    .await
+}
+
+pub struct AsyncIntcodeMachine<I, O>
+where
+    I: AsyncSource,
+    O: AsyncSink,
+{
+    machine: IntcodeMachine<ValueQueue, ValueQueue>,
+    source: I,
+    sink: O,
+}
+
+impl<I, O> AsyncIntcodeMachine<I, O>
+where
+    I: AsyncSource,
+    O: AsyncSink,
+{
+    pub fn new(tape: Tape, source: I, sink: O) -> Self {
+        AsyncIntcodeMachine {
+            machine: IntcodeMachine::new(tape, ValueQueue::new(), ValueQueue::new()),
+            source,
+            sink,
+        }
+    }
+
+    pub fn load_state(state: State, source: I, sink: O) -> Self {
+        AsyncIntcodeMachine {
+            machine: IntcodeMachine::load_state(state, ValueQueue::new(), ValueQueue::new()),
+            source,
+            sink,
+        }
+    }
+
+    pub fn dump_state(&self) -> State {
+        self.machine.dump_state()
+    }
+
+    pub async fn run(&mut self) -> Result<isize, IntcodeMachineError> {
+        let mut steps = 0;
+        loop {
+            match self.machine.step()? {
+                StepOutcome::Executed => {
+                    while let Some(value) = self.machine.output_mut().pop() {
+                        poll_fn(|cx| self.sink.poll_write(cx, value)).await;
+                    }
+                }
+                StepOutcome::AwaitingInput => match poll_fn(|cx| self.source.poll_read(cx)).await {
+                    Some(value) => self.machine.input_mut().push(value),
+                    None => {
+                        return Err(IntcodeMachineError::InputFailure(self.dump_state()));
+                    }
+                },
+                StepOutcome::Halted => return Ok(self.machine.result()),
+            }
+
+            steps += 1;
+            if steps % STEPS_BEFORE_YIELD == 0 {
+                yield_now().await;
+            }
+        }
+    }
+}
+
+type LocalTask = Pin<Box<dyn Future<Output = ()>>>;
+
+struct TaskWaker {
+    id: usize,
+    ready_queue: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready_queue.lock().unwrap().push_back(self.id);
+    }
+}
+
+pub struct TaskHandle<T>(Rc<RefCell<Option<T>>>);
+
+impl<T> TaskHandle<T> {
+    // None if the task has not finished (yet)
+    pub fn take(&self) -> Option<T> {
+        self.0.borrow_mut().take()
+    }
+}
+
+// minimal single-threaded executor, enough to multiplex many machines on a single OS thread
+#[derive(Default)]
+pub struct LocalExecutor {
+    tasks: Vec<Option<LocalTask>>,
+    ready_queue: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl LocalExecutor {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn spawn<F, T>(&mut self, future: F) -> TaskHandle<T>
+    where
+        F: Future<Output = T> + 'static,
+        T: 'static,
+    {
+        let result = Rc::new(RefCell::new(None));
+        let task_result = Rc::clone(&result);
+
+        let id = self.tasks.len();
+        self.tasks.push(Some(Box::pin(async move {
+            let output = future.await;
+            *task_result.borrow_mut() = Some(output);
+        })));
+        self.ready_queue.lock().unwrap().push_back(id);
+
+        TaskHandle(result)
+    }
+
+    // runs until no task can make any more progress and returns number of tasks
+    // that did not finish, i.e. are still waiting for something that will never happen
+    pub fn run(&mut self) -> usize {
+        loop {
+            let next = self.ready_queue.lock().unwrap().pop_front();
+            let id = match next {
+                Some(id) => id,
+                None => break,
+            };
+
+            // tasks might get woken up multiple times, including after they have already finished
+            let task = match self.tasks[id].as_mut() {
+                Some(task) => task,
+                None => continue,
+            };
+
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                ready_queue: Arc::clone(&self.ready_queue),
+            }));
+            let mut cx = Context::from_waker(&waker);
+
+            if task.as_mut().poll(&mut cx).is_ready() {
+                self.tasks[id] = None;
+            }
+        }
+
+        self.tasks.iter().filter(|task| task.is_some()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // keeps track of the last value going through while forwarding everything further
+    struct TappedSink {
+        sender: AsyncSender,
+        last_value: Rc<RefCell<Option<isize>>>,
+    }
+
+    impl AsyncSink for TappedSink {
+        fn poll_write(&mut self, cx: &mut Context<'_>, value: isize) -> Poll<()> {
+            *self.last_value.borrow_mut() = Some(value);
+            self.sender.poll_write(cx, value)
+        }
+    }
+
+    #[test]
+    fn async_machine_outputs_itself() {
+        let tape_input = vec![
+            109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+        ];
+        let (input_sender, input_receiver) = channel();
+        let (output_sender, output_receiver) = channel();
+        drop(input_sender);
+
+        let mut executor = LocalExecutor::new();
+        let handle = executor.spawn(async move {
+            AsyncIntcodeMachine::new(Tape::new(tape_input), input_receiver, output_sender)
+                .run()
+                .await
+        });
+
+        assert_eq!(0, executor.run());
+        assert!(handle.take().unwrap().is_ok());
+
+        let outputs: Vec<_> = std::iter::from_fn(|| output_receiver.try_recv()).collect();
+        assert_eq!(
+            vec![109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99],
+            outputs
+        );
+    }
+
+    #[test]
+    fn async_machine_reports_value_left_at_position_0() {
+        let (_input_sender, input_receiver) = channel();
+        let (output_sender, _output_receiver) = channel();
+
+        let mut executor = LocalExecutor::new();
+        let handle = executor.spawn(async move {
+            AsyncIntcodeMachine::new(
+                Tape::new(vec![1, 0, 0, 0, 99]),
+                input_receiver,
+                output_sender,
+            )
+            .run()
+            .await
+        });
+
+        assert_eq!(0, executor.run());
+        assert_eq!(2, handle.take().unwrap().unwrap());
+    }
+
+    #[test]
+    fn closed_source_results_in_input_failure() {
+        let (input_sender, input_receiver) = channel();
+        let (output_sender, _output_receiver) = channel();
+        drop(input_sender);
+
+        let mut executor = LocalExecutor::new();
+        let handle = executor.spawn(async move {
+            AsyncIntcodeMachine::new(Tape::new(vec![3, 0, 99]), input_receiver, output_sender)
+                .run()
+                .await
+        });
+
+        executor.run();
+        match handle.take().unwrap() {
+            Err(IntcodeMachineError::InputFailure(_)) => (),
+            other => panic!("expected input failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn feedback_loop_of_async_machines_produces_expected_signal() {
+        let tape = Tape::new(vec![
+            3, 26, 1001, 26, -4, 26, 3, 27, 1002, 27, 2, 27, 1, 27, 26, 27, 4, 27, 1001, 28, -1,
+            28, 1005, 28, 6, 99, 0, 0, 5,
+        ]);
+        let phases = [9, 8, 7, 6, 5];
+
+        let (senders, receivers): (Vec<_>, Vec<_>) = phases.iter().map(|_| channel()).unzip();
+        for (sender, &phase) in senders.iter().zip(&phases) {
+            sender.send(phase);
+        }
+        senders[0].send(0);
+
+        let last_value = Rc::new(RefCell::new(None));
+        let mut executor = LocalExecutor::new();
+        for (i, receiver) in receivers.into_iter().enumerate() {
+            let sink = TappedSink {
+                sender: senders[(i + 1) % phases.len()].clone(),
+                last_value: Rc::clone(&last_value),
+            };
+            let tape = tape.clone();
+            executor.spawn(async move {
+                AsyncIntcodeMachine::new(tape, receiver, sink)
+                    .run()
+                    .await
+                    .unwrap();
+            });
+        }
+        drop(senders);
+
+        assert_eq!(0, executor.run());
+        assert_eq!(Some(139_629_729), *last_value.borrow());
+    }
+
+    #[test]
+    fn hundreds_of_machines_can_share_a_single_thread() {
+        // repeatedly reads a value and outputs it increased by one
+        let tape = Tape::new(vec![3, 11, 101, 1, 11, 11, 4, 11, 1105, 1, 0, 0]);
+        let num_machines = 500;
+
+        let (first_sender, mut previous_receiver) = channel();
+        let mut executor = LocalExecutor::new();
+        let mut handles = Vec::new();
+        for _ in 0..num_machines {
+            let (sender, receiver) = channel();
+            let source = std::mem::replace(&mut previous_receiver, receiver);
+            let tape = tape.clone();
+            handles.push(
+                executor.spawn(async move {
+                    AsyncIntcodeMachine::new(tape, source, sender).run().await
+                }),
+            );
+        }
+
+        first_sender.send(0);
+        first_sender.send(100);
+        drop(first_sender);
+
+        // once the input is closed, every machine eventually runs out of it
+        assert_eq!(0, executor.run());
+        assert!(handles.iter().all(|handle| handle.take().unwrap().is_err()));
+
+        let outputs: Vec<_> = std::iter::from_fn(|| previous_receiver.try_recv()).collect();
+        assert_eq!(vec![500, 600], outputs);
+    }
+}
@@ -0,0 +1,27 @@
+use std::env;
+use std::path::Path;
+use std::process;
+
+use day9::corpus;
+
+const DEFAULT_CORPUS_DIR: &str = "corpus";
+
+// usage: corpus [directory]
+fn main() {
+    let dir = env::args()
+        .nth(1)
+        .unwrap_or_else(|| DEFAULT_CORPUS_DIR.to_string());
+
+    let results = match corpus::run_corpus(Path::new(&dir)) {
+        Ok(results) => results,
+        Err(err) => {
+            eprintln!("failed to load the corpus: {:?}", err);
+            process::exit(2);
+        }
+    };
+
+    print!("{}", corpus::format_table(&results));
+    if results.iter().any(|result| !result.passed()) {
+        process::exit(1);
+    }
+}
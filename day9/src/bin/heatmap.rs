@@ -0,0 +1,62 @@
+use std::env;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+use std::process;
+
+use day9::heatmap::{self, Heatmap, ImageFormat};
+use day9::intcode_machine::Tape;
+
+const BUDGET: usize = 100_000_000;
+const MAX_HEIGHT: usize = 2000;
+const MAX_WIDTH: usize = 1 << 16;
+
+fn fail(message: String) -> ! {
+    eprintln!("{}", message);
+    process::exit(1);
+}
+
+// usage: heatmap <program> <image.ppm|image.png> [comma separated inputs]
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        fail("usage: heatmap <program> <image.ppm|image.png> [inputs]".to_string());
+    }
+
+    let tape = intcode_loader::load_program(&args[1])
+        .unwrap_or_else(|err| fail(format!("failed to load {}: {}", args[1], err)));
+    let inputs = match args.get(3) {
+        Some(inputs) => intcode_loader::parse_program(inputs)
+            .unwrap_or_else(|err| fail(format!("invalid inputs: {}", err))),
+        None => Vec::new(),
+    };
+
+    let image_path = Path::new(&args[2]);
+    let format = image_path
+        .extension()
+        .and_then(|extension| ImageFormat::from_extension(&extension.to_string_lossy()))
+        .unwrap_or_else(|| fail("image has to be either .ppm or .png".to_string()));
+
+    let heatmap = heatmap::trace_program(
+        Tape::new(tape),
+        inputs,
+        BUDGET,
+        Heatmap::new()
+            .with_max_height(MAX_HEIGHT)
+            .with_max_width(MAX_WIDTH),
+    )
+    .unwrap_or_else(|err| fail(format!("program failed: {:?}", err)));
+
+    let file = File::create(image_path)
+        .unwrap_or_else(|err| fail(format!("failed to create {}: {}", args[2], err)));
+    heatmap
+        .write_image(BufWriter::new(file), format)
+        .unwrap_or_else(|err| fail(format!("failed to write {}: {}", args[2], err)));
+
+    println!(
+        "{}x{} image, {} steps per row",
+        heatmap.width(),
+        heatmap.height(),
+        heatmap.steps_per_row()
+    );
+}
@@ -0,0 +1,59 @@
+use std::env;
+use std::io::{self, BufRead, Write};
+
+use day9::intcode_machine::Tape;
+use day9::session::{Session, SessionError};
+
+const QUIT_COMMAND: &str = ":quit";
+
+fn main() {
+    let tape_path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: repl <tape file>");
+            return;
+        }
+    };
+
+    let tape = match intcode_loader::load_program(&tape_path) {
+        Ok(program) => Tape::new(program),
+        Err(err) => {
+            eprintln!("{}: {}", tape_path, err);
+            return;
+        }
+    };
+    let mut session = Session::new(tape);
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+
+    match session.start() {
+        Ok(text) => write!(stdout, "{}", text).unwrap(),
+        Err(err) => {
+            eprintln!("failed to start the program: {:?}", err);
+            return;
+        }
+    }
+    stdout.flush().unwrap();
+
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line.unwrap();
+        if line.trim() == QUIT_COMMAND {
+            break;
+        }
+
+        match session.handle_line(&line) {
+            Ok(text) => write!(stdout, "{}", text).unwrap(),
+            Err(SessionError::ReplayInterrupted { output, cause }) => {
+                write!(stdout, "{}", output).unwrap();
+                eprintln!("replay stopped, error: {:?}", cause);
+            }
+            Err(err) => eprintln!("error: {:?}", err),
+        }
+        stdout.flush().unwrap();
+
+        if session.is_halted() {
+            println!("program has halted (use :restore to go back to a saved state)");
+        }
+    }
+}
@@ -0,0 +1,47 @@
+use std::env;
+use std::io;
+use std::process;
+
+use day9::intcode_machine::{InputError, IntcodeMachine, IntcodeMachineError, LineInput, Tape};
+
+const PROMPT: &str = "> ";
+
+// usage: run <program>
+// Runs the program interactively, asking for input on the terminal. Malformed values can be
+// simply retyped, the program stops once the input gets closed.
+fn main() {
+    let path = match env::args().nth(1) {
+        Some(path) => path,
+        None => {
+            eprintln!("usage: run <program>");
+            process::exit(1);
+        }
+    };
+    let tape = match intcode_loader::load_program(&path) {
+        Ok(program) => Tape::new(program),
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        }
+    };
+
+    let input = LineInput::new(io::stdin().lock()).with_prompt(PROMPT, io::stderr());
+    let mut machine = IntcodeMachine::new(tape, input, io::stdout());
+
+    loop {
+        match machine.run() {
+            Ok(_) => return,
+            Err(IntcodeMachineError::InputError(err @ InputError::InvalidValue { .. })) => {
+                eprintln!("{}, try again", err);
+            }
+            Err(IntcodeMachineError::InputError(err)) => {
+                eprintln!("{}", err);
+                process::exit(1);
+            }
+            Err(err) => {
+                eprintln!("program failed: {:?}", err);
+                process::exit(1);
+            }
+        }
+    }
+}
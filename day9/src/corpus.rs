@@ -0,0 +1,402 @@
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::intcode_machine::{
+    IntcodeMachine, IntcodeMachineError, Spec, StepOutcome, Tape, ValueQueue,
+};
+use intcode_loader::ProgramLoadError;
+
+pub const CASE_EXTENSION: &str = "case";
+const DEFAULT_BUDGET: usize = 1_000_000;
+
+#[derive(Debug)]
+pub enum CorpusError {
+    IoError(io::Error),
+    InvalidCase {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+}
+
+impl From<io::Error> for CorpusError {
+    fn from(err: io::Error) -> Self {
+        CorpusError::IoError(err)
+    }
+}
+
+// How the run of a case ended, written in the case files in snake case
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Termination {
+    Halted,
+    TapeOutOfBounds,
+    ExecutionFailure,
+    InputFailure,
+    UnsupportedInstruction,
+    BudgetExhausted,
+}
+
+impl Termination {
+    fn parse(name: &str) -> Option<Self> {
+        use Termination::*;
+
+        match name {
+            "none" => Some(Halted),
+            "tape_out_of_bounds" => Some(TapeOutOfBounds),
+            "execution_failure" => Some(ExecutionFailure),
+            "input_failure" => Some(InputFailure),
+            "unsupported_instruction" => Some(UnsupportedInstruction),
+            "budget_exhausted" => Some(BudgetExhausted),
+            _ => None,
+        }
+    }
+}
+
+// A single case file consists of `key: value` lines, `#` starts a comment. Supported keys:
+// tape / tape_file   program given inline or as a path relative to the case file
+// patch              `address=value` pairs written into the tape before running
+// input              values fed to the program
+// spec               day2, day5 or day9 (default)
+// budget             maximum number of executed instructions
+// output             all of the expected outputs
+// memory             `address=value` pairs expected once the program stopped
+// error              expected failure, `none` (default) if the program has to halt
+#[derive(Debug, Clone, PartialEq)]
+pub struct TestCase {
+    pub name: String,
+    pub tape: Vec<isize>,
+    pub patches: Vec<(usize, isize)>,
+    pub inputs: Vec<isize>,
+    pub spec: Spec,
+    pub budget: usize,
+    pub expected_outputs: Option<Vec<isize>>,
+    pub expected_memory: Vec<(usize, isize)>,
+    pub expected_termination: Termination,
+}
+
+fn parse_values(value: &str) -> Result<Vec<isize>, String> {
+    if value.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    intcode_loader::parse_program(value).map_err(|err| err.to_string())
+}
+
+fn parse_cells(value: &str) -> Result<Vec<(usize, isize)>, String> {
+    value
+        .split(',')
+        .filter(|cell| !cell.trim().is_empty())
+        .map(|cell| {
+            let mut parts = cell.splitn(2, '=');
+            let address = parts.next().unwrap().trim().parse::<usize>();
+            let value = parts.next().map(|value| value.trim().parse::<isize>());
+            match (address, value) {
+                (Ok(address), Some(Ok(value))) => Ok((address, value)),
+                _ => Err(format!("invalid cell '{}'", cell.trim())),
+            }
+        })
+        .collect()
+}
+
+impl TestCase {
+    pub fn load(path: &Path) -> Result<Self, CorpusError> {
+        let source = fs::read_to_string(path)?;
+        let name = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+        let mut case = TestCase {
+            name,
+            tape: Vec::new(),
+            patches: Vec::new(),
+            inputs: Vec::new(),
+            spec: Spec::default(),
+            budget: DEFAULT_BUDGET,
+            expected_outputs: None,
+            expected_memory: Vec::new(),
+            expected_termination: Termination::Halted,
+        };
+
+        for (line_idx, line) in source.lines().enumerate() {
+            let invalid = |message: String| CorpusError::InvalidCase {
+                path: path.to_path_buf(),
+                line: line_idx + 1,
+                message,
+            };
+
+            let line = match line.find('#') {
+                Some(comment_start) => &line[..comment_start],
+                None => line,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            let (key, value) = match line.find(':') {
+                Some(separator) => (line[..separator].trim(), &line[separator + 1..]),
+                None => return Err(invalid(format!("expected 'key: value', got '{}'", line))),
+            };
+
+            match key {
+                "tape" => case.tape = parse_values(value).map_err(invalid)?,
+                "tape_file" => {
+                    case.tape =
+                        intcode_loader::load_program(&base_dir.join(value.trim()).to_string_lossy())
+                            .map_err(|err: ProgramLoadError| invalid(err.to_string()))?
+                }
+                "patch" => case.patches = parse_cells(value).map_err(invalid)?,
+                "input" => case.inputs = parse_values(value).map_err(invalid)?,
+                "spec" => {
+                    case.spec = match value.trim() {
+                        "day2" => Spec::Day2,
+                        "day5" => Spec::Day5,
+                        "day9" => Spec::Day9,
+                        other => return Err(invalid(format!("unknown spec '{}'", other))),
+                    }
+                }
+                "budget" => {
+                    case.budget = value
+                        .trim()
+                        .parse()
+                        .map_err(|_| invalid(format!("invalid budget '{}'", value.trim())))?
+                }
+                "output" => case.expected_outputs = Some(parse_values(value).map_err(invalid)?),
+                "memory" => case.expected_memory = parse_cells(value).map_err(invalid)?,
+                "error" => {
+                    case.expected_termination = Termination::parse(value.trim())
+                        .ok_or_else(|| invalid(format!("unknown error '{}'", value.trim())))?
+                }
+                other => return Err(invalid(format!("unknown key '{}'", other))),
+            }
+        }
+
+        if case.tape.is_empty() {
+            return Err(CorpusError::InvalidCase {
+                path: path.to_path_buf(),
+                line: 0,
+                message: "case does not define a tape".to_string(),
+            });
+        }
+        Ok(case)
+    }
+
+    pub fn run(&self) -> CaseResult {
+        let mut tape = Tape::new(self.tape.clone());
+        for &(address, value) in self.patches.iter() {
+            tape.write(address, value);
+        }
+
+        let inputs = ValueQueue::from(self.inputs.clone());
+        let mut machine = IntcodeMachine::new(tape, inputs, ValueQueue::new());
+        machine.set_spec(self.spec);
+
+        let mut termination = Termination::BudgetExhausted;
+        for _ in 0..self.budget {
+            let outcome = match machine.step() {
+                Ok(StepOutcome::Executed) => continue,
+                Ok(StepOutcome::Halted) => Termination::Halted,
+                Ok(StepOutcome::AwaitingInput)
+                | Err(IntcodeMachineError::InputFailure(_))
+                | Err(IntcodeMachineError::InputError(_)) => Termination::InputFailure,
+                Err(IntcodeMachineError::TapeOutOfBoundsError) => Termination::TapeOutOfBounds,
+                Err(IntcodeMachineError::ExecutionFailure) => Termination::ExecutionFailure,
+                Err(IntcodeMachineError::UnsupportedInstruction(_)) => {
+                    Termination::UnsupportedInstruction
+                }
+            };
+            termination = outcome;
+            break;
+        }
+
+        let outputs = machine.output_mut().drain();
+        let state = machine.dump_state();
+        let memory = state.tape().as_slice();
+
+        let mut failures = Vec::new();
+        if termination != self.expected_termination {
+            failures.push(format!(
+                "expected {:?}, got {:?}",
+                self.expected_termination, termination
+            ));
+        }
+        if let Some(expected_outputs) = self.expected_outputs.as_ref() {
+            if *expected_outputs != outputs {
+                failures.push(format!(
+                    "expected outputs {:?}, got {:?}",
+                    expected_outputs, outputs
+                ));
+            }
+        }
+        for &(address, expected) in self.expected_memory.iter() {
+            let actual = memory.get(address).cloned().unwrap_or(0);
+            if actual != expected {
+                failures.push(format!(
+                    "expected {} at {}, got {}",
+                    expected, address, actual
+                ));
+            }
+        }
+
+        CaseResult {
+            name: self.name.clone(),
+            failures,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CaseResult {
+    pub name: String,
+    // empty if the case passed
+    pub failures: Vec<String>,
+}
+
+impl CaseResult {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+// every case file in the directory, ordered by name
+pub fn load_corpus(dir: &Path) -> Result<Vec<TestCase>, CorpusError> {
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == CASE_EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+
+    paths.iter().map(|path| TestCase::load(path)).collect()
+}
+
+pub fn run_corpus(dir: &Path) -> Result<Vec<CaseResult>, CorpusError> {
+    Ok(load_corpus(dir)?.iter().map(TestCase::run).collect())
+}
+
+pub fn format_table(results: &[CaseResult]) -> String {
+    let name_width = results
+        .iter()
+        .map(|result| result.name.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut table = String::new();
+    for result in results {
+        let status = if result.passed() { "PASS" } else { "FAIL" };
+        let row = format!(
+            "{:<width$}  {}  {}",
+            result.name,
+            status,
+            result.failures.join("; "),
+            width = name_width
+        );
+        writeln!(table, "{}", row.trim_end()).unwrap();
+    }
+
+    let passed = results.iter().filter(|result| result.passed()).count();
+    writeln!(
+        table,
+        "{} passed, {} failed",
+        passed,
+        results.len() - passed
+    )
+    .unwrap();
+
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CORPUS_DIR: &str = "corpus";
+
+    fn write_case(name: &str, contents: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "day9_corpus_{}_{}.{}",
+            name,
+            std::process::id(),
+            CASE_EXTENSION
+        ));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn whole_corpus_passes() {
+        let results = run_corpus(Path::new(CORPUS_DIR)).unwrap();
+
+        assert!(!results.is_empty());
+        for result in results {
+            assert!(result.passed(), "{}: {:?}", result.name, result.failures);
+        }
+    }
+
+    #[test]
+    fn mismatches_are_reported() {
+        let path = write_case(
+            "mismatch",
+            "tape: 3, 9, 4, 9, 99 # echoes the input\ninput: 7\noutput: 8\nmemory: 9=8\n",
+        );
+        let result = TestCase::load(&path).unwrap().run();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            vec![
+                "expected outputs [8], got [7]".to_string(),
+                "expected 8 at 9, got 7".to_string()
+            ],
+            result.failures
+        );
+    }
+
+    #[test]
+    fn unexpected_termination_is_reported() {
+        let path = write_case("termination", "tape: 3, 0, 99\nerror: execution_failure\n");
+        let result = TestCase::load(&path).unwrap().run();
+        fs::remove_file(path).unwrap();
+
+        assert_eq!(
+            vec!["expected ExecutionFailure, got InputFailure".to_string()],
+            result.failures
+        );
+    }
+
+    #[test]
+    fn invalid_case_is_rejected_with_line() {
+        let path = write_case("invalid", "# comment\ntape: 1, 0, 0, 0, 99\nouput: 1\n");
+        let result = TestCase::load(&path);
+        fs::remove_file(path).unwrap();
+
+        match result {
+            Err(CorpusError::InvalidCase { line, message, .. }) => {
+                assert_eq!(3, line);
+                assert_eq!("unknown key 'ouput'", message);
+            }
+            other => panic!("expected invalid case, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn table_lists_every_case() {
+        let results = vec![
+            CaseResult {
+                name: "quine".to_string(),
+                failures: vec![],
+            },
+            CaseResult {
+                name: "io".to_string(),
+                failures: vec!["expected outputs [1], got []".to_string()],
+            },
+        ];
+
+        assert_eq!(
+            "quine  PASS\nio     FAIL  expected outputs [1], got []\n1 passed, 1 failed\n",
+            format_table(&results)
+        );
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::intcode_machine::{IntcodeMachine, IntcodeMachineError, StepOutcome, Tape, ValueQueue};
+
+const NORTH_COMMAND: isize = 1;
+const SOUTH_COMMAND: isize = 2;
+const WEST_COMMAND: isize = 3;
+const EAST_COMMAND: isize = 4;
+
+const HIT_WALL_RESPONSE: isize = 0;
+const MOVED_RESPONSE: isize = 1;
+const FOUND_TARGET_RESPONSE: isize = 2;
+
+pub type Position = (isize, isize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    North,
+    South,
+    West,
+    East,
+}
+
+impl Movement {
+    const ALL: [Movement; 4] = [
+        Movement::North,
+        Movement::South,
+        Movement::West,
+        Movement::East,
+    ];
+
+    fn command(self) -> isize {
+        match self {
+            Movement::North => NORTH_COMMAND,
+            Movement::South => SOUTH_COMMAND,
+            Movement::West => WEST_COMMAND,
+            Movement::East => EAST_COMMAND,
+        }
+    }
+
+    // y grows downwards so that the map can be rendered row by row
+    fn apply(self, (x, y): Position) -> Position {
+        match self {
+            Movement::North => (x, y - 1),
+            Movement::South => (x, y + 1),
+            Movement::West => (x - 1, y),
+            Movement::East => (x + 1, y),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cell {
+    Wall,
+    Open,
+    Target,
+}
+
+#[derive(Debug)]
+pub enum DroidError {
+    MachineFailure(IntcodeMachineError),
+    InvalidResponse(isize),
+    MissingResponse,
+    UnexpectedHalt,
+}
+
+impl From<IntcodeMachineError> for DroidError {
+    fn from(err: IntcodeMachineError) -> Self {
+        DroidError::MachineFailure(err)
+    }
+}
+
+type Droid = IntcodeMachine<ValueQueue, ValueQueue>;
+
+// issues a single movement command to a fork of the given droid and returns the response
+// together with the fork, right after it responded
+fn probe(droid: &Droid, movement: Movement) -> Result<(Cell, Droid), DroidError> {
+    let mut machine = droid.fork();
+    machine.input_mut().push(movement.command());
+
+    loop {
+        match machine.step()? {
+            StepOutcome::Executed => {
+                if let Some(response) = machine.output_mut().pop() {
+                    let cell = match response {
+                        HIT_WALL_RESPONSE => Cell::Wall,
+                        MOVED_RESPONSE => Cell::Open,
+                        FOUND_TARGET_RESPONSE => Cell::Target,
+                        _ => return Err(DroidError::InvalidResponse(response)),
+                    };
+                    return Ok((cell, machine));
+                }
+            }
+            // droid always responds before asking for the next command
+            StepOutcome::AwaitingInput => return Err(DroidError::MissingResponse),
+            StepOutcome::Halted => return Err(DroidError::UnexpectedHalt),
+        }
+    }
+}
+
+// Explores the entire reachable area with DFS. Instead of physically walking the droid back
+// after reaching a dead end, every branch simply continues from the machine forked at the
+// branching point.
+pub fn explore(tape: Tape) -> Result<MazeMap, DroidError> {
+    let origin = (0, 0);
+    let mut cells = HashMap::new();
+    cells.insert(origin, Cell::Open);
+
+    let droid = IntcodeMachine::new(tape, ValueQueue::new(), ValueQueue::new());
+    let mut stack = vec![(origin, droid)];
+    while let Some((position, droid)) = stack.pop() {
+        for &movement in Movement::ALL.iter() {
+            let next_position = movement.apply(position);
+            if cells.contains_key(&next_position) {
+                continue;
+            }
+
+            let (cell, next_droid) = probe(&droid, movement)?;
+            cells.insert(next_position, cell);
+            if cell != Cell::Wall {
+                stack.push((next_position, next_droid));
+            }
+        }
+    }
+
+    Ok(MazeMap { cells })
+}
+
+pub struct MazeMap {
+    cells: HashMap<Position, Cell>,
+}
+
+impl MazeMap {
+    pub fn cell(&self, position: Position) -> Option<Cell> {
+        self.cells.get(&position).cloned()
+    }
+
+    pub fn target(&self) -> Option<Position> {
+        self.cells
+            .iter()
+            .find(|(_, &cell)| cell == Cell::Target)
+            .map(|(&position, _)| position)
+    }
+
+    // BFS distances to every reachable cell
+    pub fn distances_from(&self, start: Position) -> HashMap<Position, usize> {
+        let mut distances = HashMap::new();
+        let mut queue = VecDeque::new();
+        distances.insert(start, 0);
+        queue.push_back(start);
+
+        while let Some(position) = queue.pop_front() {
+            let distance = distances[&position];
+            for &movement in Movement::ALL.iter() {
+                let next = movement.apply(position);
+                let passable = matches!(self.cell(next), Some(Cell::Open) | Some(Cell::Target));
+                if passable && !distances.contains_key(&next) {
+                    distances.insert(next, distance + 1);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        distances
+    }
+
+    pub fn shortest_path(&self, from: Position, to: Position) -> Option<usize> {
+        self.distances_from(from).get(&to).cloned()
+    }
+
+    // time needed for something spreading one cell per minute from `start` to fill the area
+    pub fn flood_fill_time(&self, start: Position) -> usize {
+        self.distances_from(start)
+            .values()
+            .max()
+            .cloned()
+            .unwrap_or(0)
+    }
+
+    pub fn render(&self) -> String {
+        let min_x = self.cells.keys().map(|&(x, _)| x).min().unwrap_or(0);
+        let max_x = self.cells.keys().map(|&(x, _)| x).max().unwrap_or(0);
+        let min_y = self.cells.keys().map(|&(_, y)| y).min().unwrap_or(0);
+        let max_y = self.cells.keys().map(|&(_, y)| y).max().unwrap_or(0);
+
+        let mut rendered = String::new();
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                rendered.push(match self.cell((x, y)) {
+                    _ if (x, y) == (0, 0) => 'D',
+                    Some(Cell::Wall) => '#',
+                    Some(Cell::Open) => '.',
+                    Some(Cell::Target) => 'T',
+                    None => ' ',
+                });
+            }
+            rendered.push('\n');
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // droid starting at (1, 1) of the following 5x5 grid, stored row by row at the end of
+    // the tape (0 - wall, 1 - open, 2 - target):
+    // #####
+    // #D#T#
+    // #...#
+    // #.#.#
+    // #####
+    fn maze_tape() -> Tape {
+        Tape::new(vec![
+            3, 92, 1001, 90, 0, 93, 1001, 91, 0, 94, 1008, 92, 1, 95, 1006, 95, 21, 1001, 94, -1,
+            94, 1008, 92, 2, 95, 1006, 95, 32, 1001, 94, 1, 94, 1008, 92, 3, 95, 1006, 95, 43,
+            1001, 93, -1, 93, 1008, 92, 4, 95, 1006, 95, 54, 1001, 93, 1, 93, 1002, 94, 5, 96, 1,
+            96, 93, 96, 1001, 96, 98, 96, 1001, 96, 0, 71, 1001, 0, 0, 97, 4, 97, 1006, 97, 0,
+            1001, 93, 0, 90, 1001, 94, 0, 91, 1105, 1, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            1, 0, 2, 0, 0, 1, 1, 1, 0, 0, 1, 0, 1, 0, 0, 0, 0, 0, 0,
+        ])
+    }
+
+    #[test]
+    fn exploration_discovers_entire_maze() {
+        let map = explore(maze_tape()).unwrap();
+
+        assert_eq!(Some((2, 0)), map.target());
+        // corners are never probed as they are not adjacent to any open cell
+        assert_eq!(" # # \n#D#T#\n#...#\n#.#.#\n # # \n", map.render());
+    }
+
+    #[test]
+    fn shortest_path_to_target_is_found() {
+        let map = explore(maze_tape()).unwrap();
+        assert_eq!(Some(4), map.shortest_path((0, 0), map.target().unwrap()));
+        assert_eq!(None, map.shortest_path((0, 0), (10, 10)));
+    }
+
+    #[test]
+    fn flood_fill_time_is_the_furthest_distance() {
+        let map = explore(maze_tape()).unwrap();
+        assert_eq!(4, map.flood_fill_time(map.target().unwrap()));
+        assert_eq!(4, map.flood_fill_time((0, 0)));
+    }
+
+    #[test]
+    fn invalid_response_is_reported() {
+        let tape = Tape::new(vec![3, 100, 104, 7, 1105, 1, 0]);
+        match explore(tape) {
+            Err(DroidError::InvalidResponse(7)) => (),
+            other => panic!("expected invalid response, got {:?}", other.map(|_| ())),
+        }
+    }
+}
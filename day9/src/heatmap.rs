@@ -0,0 +1,445 @@
+use std::io::{self, Write};
+
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::intcode_machine::{
+    AccessKind, IntcodeMachine, IntcodeMachineError, MemoryAccess, StepOutcome, Tape, ValueQueue,
+};
+
+const READ: u8 = 1;
+const WRITE: u8 = 2;
+const EXECUTE: u8 = 4;
+const RELATIVE_BASE: u8 = 8;
+
+// relative base can point anywhere, so it is only drawn close to the memory actually accessed
+const RELATIVE_BASE_MARGIN: usize = 16;
+
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Ppm,
+    Png,
+}
+
+impl ImageFormat {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "ppm" => Some(ImageFormat::Ppm),
+            "png" => Some(ImageFormat::Png),
+            _ => None,
+        }
+    }
+}
+
+// Memory accesses over time, one column per address and one row per `steps_per_row` steps.
+// Reads are green, writes red and executed cells blue, so that a cell both read and written
+// within a row shows up yellow. Untouched cell under the relative base is drawn grey.
+// With a maximum height set, pairs of rows get merged whenever the image would grow beyond it.
+// With a maximum width set, anything happening at higher addresses is left out.
+#[derive(Debug, Clone)]
+pub struct Heatmap {
+    rows: Vec<Vec<u8>>,
+    steps_per_row: usize,
+    steps_in_last_row: usize,
+    max_height: Option<usize>,
+    max_width: Option<usize>,
+    highest_address: Option<usize>,
+}
+
+impl Default for Heatmap {
+    fn default() -> Self {
+        Heatmap::new()
+    }
+}
+
+impl Heatmap {
+    pub fn new() -> Self {
+        Heatmap {
+            rows: Vec::new(),
+            steps_per_row: 1,
+            steps_in_last_row: 0,
+            max_height: None,
+            max_width: None,
+            highest_address: None,
+        }
+    }
+
+    pub fn with_steps_per_row(mut self, steps_per_row: usize) -> Self {
+        self.steps_per_row = steps_per_row.max(1);
+        self
+    }
+
+    pub fn with_max_height(mut self, max_height: usize) -> Self {
+        self.max_height = Some(max_height.max(1));
+        self
+    }
+
+    pub fn with_max_width(mut self, max_width: usize) -> Self {
+        self.max_width = Some(max_width.max(1));
+        self
+    }
+
+    pub fn width(&self) -> usize {
+        self.rows.iter().map(Vec::len).max().unwrap_or(0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn steps_per_row(&self) -> usize {
+        self.steps_per_row
+    }
+
+    fn halve_resolution(&mut self) {
+        let rows = std::mem::take(&mut self.rows);
+        let odd_rows = rows.len() % 2 == 1;
+
+        self.rows = rows
+            .chunks(2)
+            .map(|pair| {
+                let mut merged = pair[0].clone();
+                if let Some(second) = pair.get(1) {
+                    if second.len() > merged.len() {
+                        merged.resize(second.len(), 0);
+                    }
+                    for (cell, flags) in merged.iter_mut().zip(second.iter()) {
+                        *cell |= flags;
+                    }
+                }
+                merged
+            })
+            .collect();
+        self.steps_per_row *= 2;
+        // the last row is only half full if it didn't have a pair
+        self.steps_in_last_row = if odd_rows {
+            self.steps_per_row / 2
+        } else {
+            self.steps_per_row
+        };
+    }
+
+    fn start_step(&mut self) {
+        if !self.rows.is_empty() && self.steps_in_last_row < self.steps_per_row {
+            return;
+        }
+        if self.max_height == Some(self.rows.len()) {
+            self.halve_resolution();
+            if self.steps_in_last_row < self.steps_per_row {
+                return;
+            }
+        }
+        self.rows.push(Vec::new());
+        self.steps_in_last_row = 0;
+    }
+
+    fn mark(&mut self, address: usize, flag: u8) {
+        if self.max_width.is_some_and(|max_width| address >= max_width) {
+            return;
+        }
+        let row = self.rows.last_mut().unwrap();
+        if address >= row.len() {
+            row.resize(address + 1, 0);
+        }
+        row[address] |= flag;
+    }
+
+    // accesses made by a single step together with the relative base once it got executed
+    pub fn record_step(&mut self, accesses: &[MemoryAccess], relative_base: isize) {
+        self.start_step();
+        for access in accesses {
+            let flag = match access.kind {
+                AccessKind::Execute => EXECUTE,
+                AccessKind::Read => READ,
+                AccessKind::Write { .. } => WRITE,
+            };
+            self.highest_address = self.highest_address.max(Some(access.address));
+            self.mark(access.address, flag);
+        }
+
+        let near_accessed_memory = |address: usize| {
+            self.highest_address
+                .is_some_and(|highest| address <= highest.saturating_add(RELATIVE_BASE_MARGIN))
+        };
+        if relative_base >= 0 && near_accessed_memory(relative_base as usize) {
+            self.mark(relative_base as usize, RELATIVE_BASE);
+        }
+        self.steps_in_last_row += 1;
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> [u8; 3] {
+        let flags = self
+            .rows
+            .get(y)
+            .and_then(|row| row.get(x))
+            .cloned()
+            .unwrap_or(0);
+        let channel = |flag| if flags & flag != 0 { 255 } else { 0 };
+
+        if flags & (READ | WRITE | EXECUTE) == 0 && flags & RELATIVE_BASE != 0 {
+            [96, 96, 96]
+        } else {
+            [channel(WRITE), channel(READ), channel(EXECUTE)]
+        }
+    }
+
+    // empty heatmap is still drawn as a single black pixel as images can't be empty
+    fn dimensions(&self) -> (usize, usize) {
+        (self.width().max(1), self.height().max(1))
+    }
+
+    fn raw_rows(&self) -> impl Iterator<Item = Vec<u8>> + '_ {
+        let (width, height) = self.dimensions();
+        (0..height).map(move |y| (0..width).flat_map(|x| self.pixel(x, y)).collect())
+    }
+
+    pub fn write_ppm<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let (width, height) = self.dimensions();
+        write!(writer, "P6\n{} {}\n255\n", width, height)?;
+        for row in self.raw_rows() {
+            writer.write_all(&row)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_png<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let (width, height) = self.dimensions();
+
+        let mut header = Vec::with_capacity(13);
+        header.extend_from_slice(&(width as u32).to_be_bytes());
+        header.extend_from_slice(&(height as u32).to_be_bytes());
+        // 8 bit truecolour, default compression and filtering, no interlacing
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        for row in self.raw_rows() {
+            // every scanline starts with its filter type, none in this case
+            encoder.write_all(&[0])?;
+            encoder.write_all(&row)?;
+        }
+        let data = encoder.finish()?;
+
+        writer.write_all(&PNG_SIGNATURE)?;
+        write_png_chunk(&mut writer, b"IHDR", &header)?;
+        write_png_chunk(&mut writer, b"IDAT", &data)?;
+        write_png_chunk(&mut writer, b"IEND", &[])
+    }
+
+    pub fn write_image<W: Write>(&self, writer: W, format: ImageFormat) -> io::Result<()> {
+        match format {
+            ImageFormat::Ppm => self.write_ppm(writer),
+            ImageFormat::Png => self.write_png(writer),
+        }
+    }
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn write_png_chunk<W: Write>(writer: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    let mut checked = Vec::with_capacity(kind.len() + data.len());
+    checked.extend_from_slice(kind);
+    checked.extend_from_slice(data);
+
+    writer.write_all(&(data.len() as u32).to_be_bytes())?;
+    writer.write_all(&checked)?;
+    writer.write_all(&crc32(&checked).to_be_bytes())
+}
+
+// Runs the program for at most `budget` steps, or until it halts or runs out of input,
+// recording its accesses into the heatmap. Waiting for input is not a step of its own.
+pub fn trace_program(
+    tape: Tape,
+    inputs: Vec<isize>,
+    budget: usize,
+    mut heatmap: Heatmap,
+) -> Result<Heatmap, IntcodeMachineError> {
+    let mut machine = IntcodeMachine::new(tape, ValueQueue::from(inputs), ValueQueue::new());
+    machine.enable_tracing();
+
+    for _ in 0..budget {
+        let outcome = machine.step()?;
+        if outcome == StepOutcome::AwaitingInput {
+            break;
+        }
+        heatmap.record_step(&machine.take_trace(), machine.relative_base());
+        if outcome == StepOutcome::Halted {
+            break;
+        }
+    }
+
+    Ok(heatmap)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+    use flate2::read::ZlibDecoder;
+    use std::io::Read;
+
+    const BLACK: [u8; 3] = [0, 0, 0];
+    const BLUE: [u8; 3] = [0, 0, 255];
+    const GREEN: [u8; 3] = [0, 255, 0];
+    const RED: [u8; 3] = [255, 0, 0];
+    const GREY: [u8; 3] = [96, 96, 96];
+
+    fn row(heatmap: &Heatmap, y: usize) -> Vec<[u8; 3]> {
+        (0..heatmap.width()).map(|x| heatmap.pixel(x, y)).collect()
+    }
+
+    #[test]
+    fn accesses_are_coloured_by_kind() {
+        // adds the cells 5 and 6 into 7, then halts
+        let tape = Tape::new(vec![1, 5, 6, 7, 99, 10, 20, 0]);
+        let heatmap = trace_program(tape, vec![], 100, Heatmap::new()).unwrap();
+
+        assert_eq!(2, heatmap.height());
+        // the opcode cell is under the relative base too, but executing it takes precedence
+        assert_eq!(
+            vec![BLUE, BLUE, BLUE, BLUE, BLACK, GREEN, GREEN, RED],
+            row(&heatmap, 0)
+        );
+        assert_eq!(
+            vec![GREY, BLACK, BLACK, BLACK, BLUE, BLACK, BLACK, BLACK],
+            row(&heatmap, 1)
+        );
+    }
+
+    #[test]
+    fn read_and_write_of_the_same_cell_mix_colours() {
+        // doubles the cell 9 in place, with the relative base moved onto it first
+        let tape = Tape::new(vec![109, 9, 1, 9, 9, 9, 99, 0, 0, 4]);
+        let heatmap =
+            trace_program(tape, vec![], 100, Heatmap::new().with_steps_per_row(3)).unwrap();
+
+        assert_eq!(1, heatmap.height());
+        assert_eq!([255, 255, 0], heatmap.pixel(9, 0));
+    }
+
+    #[test]
+    fn rows_are_merged_to_stay_within_maximum_height() {
+        let tape = Tape::new(utils::read_input_file("day9.input"));
+        let full = trace_program(tape.clone(), vec![1], 1000, Heatmap::new()).unwrap();
+        let limited =
+            trace_program(tape, vec![1], 1000, Heatmap::new().with_max_height(10)).unwrap();
+
+        assert!(full.height() > 10);
+        assert!(limited.height() <= 10);
+        assert_eq!(
+            full.height().div_ceil(limited.steps_per_row()),
+            limited.height()
+        );
+        // merging keeps every access that happened within the merged rows
+        for x in 0..full.width() {
+            let touched = (0..full.height()).any(|y| full.pixel(x, y) != BLACK);
+            let limited_touched = (0..limited.height()).any(|y| limited.pixel(x, y) != BLACK);
+            assert_eq!(touched, limited_touched);
+        }
+    }
+
+    #[test]
+    fn distant_relative_base_does_not_widen_the_image() {
+        let tape = Tape::new(vec![109, 1_000_000_000_000, 99]);
+        let heatmap = trace_program(tape, vec![], 100, Heatmap::new()).unwrap();
+
+        assert_eq!(2, heatmap.height());
+        assert_eq!(3, heatmap.width());
+    }
+
+    #[test]
+    fn accesses_beyond_maximum_width_are_left_out() {
+        let tape = Tape::new(vec![1, 5, 6, 7, 99, 10, 20, 0]);
+        let heatmap = trace_program(tape, vec![], 100, Heatmap::new().with_max_width(6)).unwrap();
+
+        assert_eq!(6, heatmap.width());
+        assert_eq!(vec![BLUE, BLUE, BLUE, BLUE, BLACK, GREEN], row(&heatmap, 0));
+    }
+
+    #[test]
+    fn waiting_for_input_is_not_recorded() {
+        // outputs a value and then asks for input that never comes
+        let tape = Tape::new(vec![104, 1, 3, 0, 99]);
+        let heatmap = trace_program(tape, vec![], 100, Heatmap::new()).unwrap();
+
+        assert_eq!(1, heatmap.height());
+    }
+
+    #[test]
+    fn ppm_contains_header_and_pixels() {
+        let tape = Tape::new(vec![104, 7, 99]);
+        let heatmap = trace_program(tape, vec![], 100, Heatmap::new()).unwrap();
+
+        let mut ppm = Vec::new();
+        heatmap.write_ppm(&mut ppm).unwrap();
+
+        let mut expected = b"P6\n3 2\n255\n".to_vec();
+        for pixel in [BLUE, BLUE, BLACK, GREY, BLACK, BLUE] {
+            expected.extend_from_slice(&pixel);
+        }
+        assert_eq!(expected, ppm);
+    }
+
+    #[test]
+    fn crc_matches_reference_values() {
+        assert_eq!(0xae42_6082, crc32(b"IEND"));
+        assert_eq!(0xcbf4_3926, crc32(b"123456789"));
+    }
+
+    #[test]
+    fn png_chunks_hold_the_same_pixels_as_ppm() {
+        let tape = Tape::new(vec![104, 7, 99]);
+        let heatmap = trace_program(tape, vec![], 100, Heatmap::new()).unwrap();
+
+        let mut png = Vec::new();
+        heatmap.write_image(&mut png, ImageFormat::Png).unwrap();
+        assert_eq!(PNG_SIGNATURE, png[..8]);
+
+        // IHDR, IDAT and IEND, each as length, type, data and crc
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+            let checked = &rest[4..8 + len];
+            let crc =
+                u32::from_be_bytes([rest[8 + len], rest[9 + len], rest[10 + len], rest[11 + len]]);
+            assert_eq!(crc32(checked), crc);
+            chunks.push((checked[..4].to_vec(), checked[4..].to_vec()));
+            rest = &rest[12 + len..];
+        }
+
+        let kinds: Vec<_> = chunks.iter().map(|(kind, _)| kind.as_slice()).collect();
+        assert_eq!(vec![&b"IHDR"[..], b"IDAT", b"IEND"], kinds);
+        assert_eq!(vec![0, 0, 0, 3, 0, 0, 0, 2, 8, 2, 0, 0, 0], chunks[0].1);
+
+        let mut scanlines = Vec::new();
+        ZlibDecoder::new(&chunks[1].1[..])
+            .read_to_end(&mut scanlines)
+            .unwrap();
+        let mut ppm = Vec::new();
+        heatmap.write_ppm(&mut ppm).unwrap();
+        let pixels = &ppm[b"P6\n3 2\n255\n".len()..];
+        assert_eq!([&[0], &pixels[..9], &[0], &pixels[9..]].concat(), scanlines);
+    }
+
+    #[test]
+    fn format_is_chosen_by_extension() {
+        assert_eq!(Some(ImageFormat::Png), ImageFormat::from_extension("PNG"));
+        assert_eq!(Some(ImageFormat::Ppm), ImageFormat::from_extension("ppm"));
+        assert_eq!(None, ImageFormat::from_extension("bmp"));
+    }
+}
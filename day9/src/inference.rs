@@ -0,0 +1,547 @@
+use std::cmp::Reverse;
+use std::fmt;
+
+use crate::input_search::Patch;
+use crate::intcode_machine::{IntcodeMachine, IntcodeMachineError, StepOutcome, Tape, ValueQueue};
+
+// differences smaller than that are treated as floating point noise
+const EPSILON: f64 = 1e-6;
+// same for errors of a model, relative to the largest of the observed values
+const RELATIVE_EPSILON: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Observable {
+    // value of the memory cell once the program halted
+    Memory(usize),
+    // n-th value written by the program
+    Output(usize),
+}
+
+#[derive(Debug)]
+pub enum InferenceError {
+    MachineFailure(IntcodeMachineError),
+    AwaitingInput,
+    BudgetExhausted,
+    MissingObservation,
+    // none of the models up to the maximum degree agreed with all of the samples,
+    // the error is relative to the largest of the observed values
+    NoModelFound { max_error: f64 },
+}
+
+impl From<IntcodeMachineError> for InferenceError {
+    fn from(err: IntcodeMachineError) -> Self {
+        InferenceError::MachineFailure(err)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct InferenceConfig {
+    pub inputs: Vec<isize>,
+    pub budget: usize,
+    pub max_degree: u32,
+    // sampled values of every variable, the fit uses the full grid of them
+    pub samples_per_variable: usize,
+}
+
+impl Default for InferenceConfig {
+    fn default() -> Self {
+        InferenceConfig {
+            inputs: Vec::new(),
+            budget: 100_000,
+            max_degree: 2,
+            samples_per_variable: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Term {
+    pub coefficient: f64,
+    // power of every variable, in the same order as the variables
+    pub exponents: Vec<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Model {
+    variables: Vec<usize>,
+    observable: Observable,
+    terms: Vec<Term>,
+}
+
+impl Model {
+    pub fn terms(&self) -> &[Term] {
+        &self.terms
+    }
+
+    pub fn degree(&self) -> u32 {
+        self.terms
+            .iter()
+            .map(|term| term.exponents.iter().sum())
+            .max()
+            .unwrap_or(0)
+    }
+
+    pub fn is_integral(&self) -> bool {
+        self.terms
+            .iter()
+            .all(|term| term.coefficient.fract() == 0.0)
+    }
+
+    // values have to be given in the same order as the variables
+    pub fn evaluate(&self, values: &[isize]) -> f64 {
+        self.terms
+            .iter()
+            .map(|term| term.coefficient * monomial(&term.exponents, values))
+            .sum()
+    }
+
+    // same model with every coefficient rounded to the nearest integer
+    fn rounded(&self) -> Model {
+        let terms = self
+            .terms
+            .iter()
+            .map(|term| Term {
+                coefficient: term.coefficient.round(),
+                exponents: term.exponents.clone(),
+            })
+            .filter(|term| term.coefficient != 0.0)
+            .collect();
+        Model {
+            terms,
+            ..self.clone()
+        }
+    }
+}
+
+impl fmt::Display for Model {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.observable {
+            Observable::Memory(address) => write!(f, "[{}] =", address)?,
+            Observable::Output(index) => write!(f, "out{} =", index)?,
+        }
+
+        let mut first = true;
+        for term in self.terms.iter() {
+            let sign = if term.coefficient < 0.0 { "-" } else { "+" };
+            if first {
+                write!(f, " {}", if sign == "-" { "-" } else { "" })?;
+            } else {
+                write!(f, " {} ", sign)?;
+            }
+            first = false;
+
+            let factors: Vec<_> = self
+                .variables
+                .iter()
+                .zip(term.exponents.iter())
+                .filter(|(_, &exponent)| exponent > 0)
+                .map(|(address, &exponent)| match exponent {
+                    1 => format!("[{}]", address),
+                    _ => format!("[{}]^{}", address, exponent),
+                })
+                .collect();
+
+            let coefficient = term.coefficient.abs();
+            if factors.is_empty() {
+                write!(f, "{}", coefficient)?;
+            } else if coefficient == 1.0 {
+                write!(f, "{}", factors.join("*"))?;
+            } else {
+                write!(f, "{}*{}", coefficient, factors.join("*"))?;
+            }
+        }
+        if first {
+            write!(f, " 0")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct Inference {
+    pub model: Model,
+    pub fitted_samples: usize,
+    pub verified_samples: usize,
+}
+
+fn monomial(exponents: &[u32], values: &[isize]) -> f64 {
+    exponents
+        .iter()
+        .zip(values.iter())
+        .map(|(&exponent, &value)| (value as f64).powi(exponent as i32))
+        .product()
+}
+
+// every combination of exponents with their sum not exceeding the degree, constant term first
+fn exponents_up_to(variables: usize, degree: u32) -> Vec<Vec<u32>> {
+    let mut all = vec![vec![]];
+    for _ in 0..variables {
+        all = all
+            .into_iter()
+            .flat_map(|prefix: Vec<u32>| {
+                let used: u32 = prefix.iter().sum();
+                (0..=degree - used).map(move |exponent| {
+                    let mut exponents = prefix.clone();
+                    exponents.push(exponent);
+                    exponents
+                })
+            })
+            .collect();
+    }
+    all.sort_by_key(|exponents| (exponents.iter().sum::<u32>(), Reverse(exponents.clone())));
+    all
+}
+
+// evenly spread values of the range, including both of its ends
+fn spread(values: &std::ops::RangeInclusive<isize>, count: usize) -> Vec<isize> {
+    if values.is_empty() {
+        return Vec::new();
+    }
+    let (start, end) = (*values.start() as i128, *values.end() as i128);
+    let count = count.max(2) as i128;
+
+    let mut spread: Vec<_> = (0..count)
+        .map(|i| (start + (end - start) * i / (count - 1)) as isize)
+        .collect();
+    spread.dedup();
+    spread
+}
+
+// values lying strictly between the ones spread over the range, two in every gap
+fn between(values: &std::ops::RangeInclusive<isize>, count: usize) -> Vec<isize> {
+    let spread_values = spread(values, count);
+    let gaps = spread_values.len().saturating_sub(1);
+    spread(values, 3 * gaps + 1)
+        .into_iter()
+        .filter(|value| !spread_values.contains(value))
+        .collect()
+}
+
+fn grid(
+    variables: &[Patch],
+    count: usize,
+    axis: fn(&std::ops::RangeInclusive<isize>, usize) -> Vec<isize>,
+) -> Vec<Vec<isize>> {
+    let mut grid = vec![vec![]];
+    for variable in variables {
+        let values = axis(&variable.values, count);
+        grid = grid
+            .into_iter()
+            .flat_map(|prefix| {
+                values.iter().map(move |&value| {
+                    let mut point = prefix.clone();
+                    point.push(value);
+                    point
+                })
+            })
+            .collect();
+    }
+    grid
+}
+
+fn observe(
+    tape: &Tape,
+    variables: &[Patch],
+    point: &[isize],
+    observable: Observable,
+    config: &InferenceConfig,
+) -> Result<isize, InferenceError> {
+    let mut tape = tape.clone();
+    for (variable, &value) in variables.iter().zip(point.iter()) {
+        tape.write(variable.address, value);
+    }
+
+    let inputs = ValueQueue::from(config.inputs.clone());
+    let mut machine = IntcodeMachine::new(tape, inputs, ValueQueue::new());
+    for _ in 0..config.budget {
+        match machine.step()? {
+            StepOutcome::Executed => (),
+            StepOutcome::AwaitingInput => return Err(InferenceError::AwaitingInput),
+            StepOutcome::Halted => {
+                let observation = match observable {
+                    Observable::Memory(address) => {
+                        machine.dump_state().tape().as_slice().get(address).cloned()
+                    }
+                    Observable::Output(index) => machine.output_mut().drain().get(index).cloned(),
+                };
+                return observation.ok_or(InferenceError::MissingObservation);
+            }
+        }
+    }
+
+    Err(InferenceError::BudgetExhausted)
+}
+
+// least squares fit through normal equations, None if the system turns out to be singular
+fn least_squares(rows: &[Vec<f64>], targets: &[f64]) -> Option<Vec<f64>> {
+    let n = rows.first()?.len();
+    let mut system = vec![vec![0.0; n + 1]; n];
+    for (row, &target) in rows.iter().zip(targets.iter()) {
+        for i in 0..n {
+            for j in 0..n {
+                system[i][j] += row[i] * row[j];
+            }
+            system[i][n] += row[i] * target;
+        }
+    }
+
+    // samples too large for f64 can't be fitted
+    if system.iter().flatten().any(|value| !value.is_finite()) {
+        return None;
+    }
+
+    // gaussian elimination with partial pivoting
+    for column in 0..n {
+        let pivot = (column..n)
+            .max_by(|&a, &b| system[a][column].abs().total_cmp(&system[b][column].abs()))?;
+        if system[pivot][column].abs() < EPSILON {
+            return None;
+        }
+        system.swap(column, pivot);
+
+        let pivot_row = system[column].clone();
+        for (i, row) in system.iter_mut().enumerate() {
+            if i != column {
+                let factor = row[column] / pivot_row[column];
+                for (value, pivot_value) in row.iter_mut().zip(pivot_row.iter()).skip(column) {
+                    *value -= factor * pivot_value;
+                }
+            }
+        }
+    }
+
+    Some((0..n).map(|i| system[i][n] / system[i][i]).collect())
+}
+
+fn fit(
+    variables: &[Patch],
+    observable: Observable,
+    degree: u32,
+    samples: &[(Vec<isize>, isize)],
+) -> Option<Model> {
+    let exponents = exponents_up_to(variables.len(), degree);
+    let rows: Vec<Vec<f64>> = samples
+        .iter()
+        .map(|(point, _)| exponents.iter().map(|e| monomial(e, point)).collect())
+        .collect();
+    let targets: Vec<f64> = samples.iter().map(|&(_, value)| value as f64).collect();
+
+    let coefficients = least_squares(&rows, &targets)?;
+    let terms = exponents
+        .into_iter()
+        .zip(coefficients)
+        .map(|(exponents, coefficient)| {
+            // programs tend to compute integer formulas, so get rid of the rounding errors
+            let rounded = coefficient.round();
+            let coefficient = if (coefficient - rounded).abs() < EPSILON {
+                rounded
+            } else {
+                coefficient
+            };
+            Term {
+                coefficient,
+                exponents,
+            }
+        })
+        .filter(|term| term.coefficient != 0.0)
+        .collect();
+
+    Some(Model {
+        variables: variables.iter().map(|variable| variable.address).collect(),
+        observable,
+        terms,
+    })
+}
+
+fn max_error(model: &Model, samples: &[(Vec<isize>, isize)]) -> f64 {
+    samples
+        .iter()
+        .map(|(point, value)| (model.evaluate(point) - *value as f64).abs())
+        .fold(0.0, f64::max)
+}
+
+// Finds the lowest degree polynomial of the variables that explains the observed value. The model
+// is fitted on a grid of samples and then checked against a denser grid of points lying between
+// the fitted ones.
+pub fn infer(
+    tape: &Tape,
+    variables: &[Patch],
+    observable: Observable,
+    config: &InferenceConfig,
+) -> Result<Inference, InferenceError> {
+    let samples_per_variable = config
+        .samples_per_variable
+        .max(config.max_degree as usize + 1);
+    let sample = |axis| -> Result<Vec<(Vec<isize>, isize)>, InferenceError> {
+        grid(variables, samples_per_variable, axis)
+            .into_iter()
+            .map(|point| {
+                let value = observe(tape, variables, &point, observable, config)?;
+                Ok((point, value))
+            })
+            .collect()
+    };
+
+    let fitting = sample(spread)?;
+    let verification = sample(between)?;
+
+    let scale = fitting
+        .iter()
+        .chain(verification.iter())
+        .map(|&(_, value)| (value as f64).abs())
+        .fold(1.0, f64::max);
+    let relative_error =
+        |model: &Model| max_error(model, &fitting).max(max_error(model, &verification)) / scale;
+
+    let mut best_error = f64::INFINITY;
+    for degree in 0..=config.max_degree {
+        let model = match fit(variables, observable, degree, &fitting) {
+            Some(model) => model,
+            None => continue,
+        };
+
+        // large values leave noise in the coefficients that rounding can't tell apart
+        // from actual fractions, so an integral model is preferred whenever it fits as well
+        let rounded = model.rounded();
+        let model = if relative_error(&rounded) < RELATIVE_EPSILON {
+            rounded
+        } else {
+            model
+        };
+
+        let error = relative_error(&model);
+        if error < RELATIVE_EPSILON {
+            return Ok(Inference {
+                model,
+                fitted_samples: fitting.len(),
+                verified_samples: verification.len(),
+            });
+        }
+        best_error = best_error.min(error);
+    }
+
+    Err(InferenceError::NoModelFound {
+        max_error: best_error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn day2_output_is_linear_in_noun_and_verb() {
+        let tape = Tape::new(utils::read_input_file("day2.input"));
+        let variables = vec![Patch::new(1, 0..=99), Patch::new(2, 0..=99)];
+
+        let inference = infer(
+            &tape,
+            &variables,
+            Observable::Memory(0),
+            &InferenceConfig::default(),
+        )
+        .unwrap();
+        let model = inference.model;
+
+        assert_eq!(1, model.degree());
+        assert!(model.is_integral());
+        assert_eq!(4_138_687.0, model.evaluate(&[12, 2]));
+        assert_eq!(19_690_720.0, model.evaluate(&[66, 35]));
+        assert_eq!("[0] = 682685 + 288000*[1] + [2]", model.to_string());
+    }
+
+    #[test]
+    fn quadratic_output_is_recovered() {
+        // prints 3 - x^2 - 2x, with x stored at 23
+        let mut program = vec![
+            2, 23, 23, 24, 1002, 23, -2, 25, 1002, 24, -1, 24, 1, 24, 25, 24, 1001, 24, 3, 24, 4,
+            24, 99,
+        ];
+        program.resize(26, 0);
+        let tape = Tape::new(program);
+
+        let inference = infer(
+            &tape,
+            &[Patch::new(23, -50..=50)],
+            Observable::Output(0),
+            &InferenceConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(2, inference.model.degree());
+        assert_eq!("out0 = 3 - 2*[23] - [23]^2", inference.model.to_string());
+        assert!(inference.verified_samples > inference.fitted_samples);
+    }
+
+    #[test]
+    fn non_polynomial_behaviour_is_reported() {
+        // outputs 1 when the value at 9 is less than 7, 0 otherwise
+        let tape = Tape::new(vec![1007, 9, 7, 10, 4, 10, 99, 0, 0, 0, 0]);
+
+        match infer(
+            &tape,
+            &[Patch::new(9, -100..=100)],
+            Observable::Output(0),
+            &InferenceConfig::default(),
+        ) {
+            Err(InferenceError::NoModelFound { max_error }) => assert!(max_error > 0.0),
+            other => panic!("expected no model to be found, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn exponents_are_enumerated_by_total_degree() {
+        assert_eq!(
+            vec![
+                vec![0, 0],
+                vec![1, 0],
+                vec![0, 1],
+                vec![2, 0],
+                vec![1, 1],
+                vec![0, 2]
+            ],
+            exponents_up_to(2, 2)
+        );
+    }
+
+    #[test]
+    fn verification_points_are_not_fitted_ones() {
+        let variables = vec![Patch::new(1, 0..=99), Patch::new(2, -50..=50)];
+        let fitting = grid(&variables, 5, spread);
+        let verification = grid(&variables, 5, between);
+
+        assert_eq!(25, fitting.len());
+        assert_eq!(64, verification.len());
+        assert!(verification.iter().all(|point| !fitting.contains(point)));
+    }
+
+    #[test]
+    fn large_values_are_compared_relatively() {
+        // outputs 123456789x^2 + 7, with x stored at 15
+        let tape = Tape::new(vec![
+            2, 15, 15, 16, 1002, 16, 123456789, 16, 1001, 16, 7, 16, 4, 16, 99, 0, 0,
+        ]);
+
+        let inference = infer(
+            &tape,
+            &[Patch::new(15, -1_000..=1_000)],
+            Observable::Output(0),
+            &InferenceConfig::default(),
+        )
+        .unwrap();
+
+        assert_eq!(2, inference.model.degree());
+        assert_eq!("out0 = 7 + 123456789*[15]^2", inference.model.to_string());
+    }
+
+    #[test]
+    fn non_finite_systems_are_not_solved() {
+        let rows = vec![vec![1.0, f64::MAX], vec![1.0, f64::MAX]];
+        assert_eq!(None, least_squares(&rows, &[1.0, 2.0]));
+
+        let rows = vec![vec![1.0, f64::NAN], vec![1.0, 2.0]];
+        assert_eq!(None, least_squares(&rows, &[1.0, 2.0]));
+    }
+}
@@ -0,0 +1,352 @@
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::intcode_machine::{IntcodeMachine, Spec, StepOutcome, Tape, ValueQueue};
+
+// Address that gets overwritten before the program starts, with every value of the range tried
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    pub address: usize,
+    pub values: RangeInclusive<isize>,
+}
+
+impl Patch {
+    pub fn new(address: usize, values: RangeInclusive<isize>) -> Self {
+        Patch { address, values }
+    }
+
+    fn len(&self) -> usize {
+        if self.values.is_empty() {
+            0
+        } else {
+            // Wrapping difference reinterpreted as unsigned is exact even for the widest ranges,
+            // only the full range of isize has one more value than usize can count.
+            (self.values.end().wrapping_sub(*self.values.start()) as usize).saturating_add(1)
+        }
+    }
+
+    // offsets beyond isize::MAX wrap around to the right value in two's complement
+    fn value(&self, offset: usize) -> isize {
+        self.values.start().wrapping_add(offset as isize)
+    }
+}
+
+// Final state of a candidate program that halted
+pub struct Outcome<'a> {
+    pub memory: &'a [isize],
+    pub outputs: &'a [isize],
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchMode {
+    First,
+    All,
+}
+
+#[derive(Debug, Clone)]
+pub struct SearchConfig {
+    pub inputs: Vec<isize>,
+    // maximum number of instructions executed by a single candidate
+    pub budget: usize,
+    pub spec: Spec,
+    pub mode: SearchMode,
+    pub workers: usize,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        SearchConfig {
+            inputs: Vec::new(),
+            budget: 100_000,
+            spec: Spec::default(),
+            mode: SearchMode::First,
+            workers: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    // (address, value) in the same order as the patches
+    pub values: Vec<(usize, isize)>,
+}
+
+impl Assignment {
+    pub fn value(&self, address: usize) -> Option<isize> {
+        self.values
+            .iter()
+            .find(|(patched, _)| *patched == address)
+            .map(|&(_, value)| value)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct SearchReport {
+    // ordered as if the candidates were tried one by one, last patch changing the fastest
+    pub assignments: Vec<Assignment>,
+    pub evaluated: usize,
+    pub failed: usize,
+    pub budget_exhausted: usize,
+}
+
+enum CandidateResult {
+    Matched,
+    Rejected,
+    Failed,
+    BudgetExhausted,
+}
+
+struct SharedSearch<'a, P> {
+    tape: &'a Tape,
+    patches: &'a [Patch],
+    target: &'a P,
+    config: &'a SearchConfig,
+    candidates: usize,
+    next_candidate: AtomicUsize,
+    // lowest index of a matching candidate, so that searching for the first one can stop early
+    first_match: AtomicUsize,
+    report: Mutex<(Vec<usize>, SearchReport)>,
+}
+
+impl<'a, P> SharedSearch<'a, P>
+where
+    P: Fn(&Outcome) -> bool + Sync,
+{
+    fn assignment(&self, mut candidate: usize) -> Assignment {
+        let mut values = vec![(0, 0); self.patches.len()];
+        for (i, patch) in self.patches.iter().enumerate().rev() {
+            let offset = candidate % patch.len();
+            candidate /= patch.len();
+            values[i] = (patch.address, patch.value(offset));
+        }
+        Assignment { values }
+    }
+
+    fn evaluate(&self, assignment: &Assignment) -> CandidateResult {
+        let mut tape = self.tape.clone();
+        for &(address, value) in assignment.values.iter() {
+            tape.write(address, value);
+        }
+
+        let inputs = ValueQueue::from(self.config.inputs.clone());
+        let mut machine = IntcodeMachine::new(tape, inputs, ValueQueue::new());
+        machine.set_spec(self.config.spec);
+
+        for _ in 0..self.config.budget {
+            match machine.step() {
+                Ok(StepOutcome::Executed) => (),
+                Ok(StepOutcome::Halted) => {
+                    let outputs = machine.output_mut().drain();
+                    let state = machine.dump_state();
+                    let outcome = Outcome {
+                        memory: state.tape().as_slice(),
+                        outputs: &outputs,
+                    };
+                    return if (self.target)(&outcome) {
+                        CandidateResult::Matched
+                    } else {
+                        CandidateResult::Rejected
+                    };
+                }
+                Ok(StepOutcome::AwaitingInput) | Err(_) => return CandidateResult::Failed,
+            }
+        }
+        CandidateResult::BudgetExhausted
+    }
+
+    fn work(&self) {
+        loop {
+            let candidate = self.next_candidate.fetch_add(1, Ordering::SeqCst);
+            if candidate >= self.candidates {
+                return;
+            }
+            if self.config.mode == SearchMode::First
+                && candidate > self.first_match.load(Ordering::SeqCst)
+            {
+                return;
+            }
+
+            let result = self.evaluate(&self.assignment(candidate));
+
+            let mut guard = self.report.lock().unwrap();
+            let (matches, report) = &mut *guard;
+            report.evaluated += 1;
+            match result {
+                CandidateResult::Matched => {
+                    matches.push(candidate);
+                    self.first_match.fetch_min(candidate, Ordering::SeqCst);
+                }
+                CandidateResult::Rejected => (),
+                CandidateResult::Failed => report.failed += 1,
+                CandidateResult::BudgetExhausted => report.budget_exhausted += 1,
+            }
+        }
+    }
+}
+
+// Tries every combination of the patched values, keeping the ones for which the program halts
+// within the budget and its final memory and outputs satisfy the target.
+pub fn search<P>(tape: &Tape, patches: &[Patch], target: P, config: &SearchConfig) -> SearchReport
+where
+    P: Fn(&Outcome) -> bool + Sync,
+{
+    let candidates = patches
+        .iter()
+        .map(Patch::len)
+        .fold(1usize, |acc, len| acc.saturating_mul(len));
+
+    let search = SharedSearch {
+        tape,
+        patches,
+        target: &target,
+        config,
+        candidates,
+        next_candidate: AtomicUsize::new(0),
+        first_match: AtomicUsize::new(usize::MAX),
+        report: Mutex::new((Vec::new(), SearchReport::default())),
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..config.workers.max(1) {
+            scope.spawn(|| search.work());
+        }
+    });
+
+    let (mut matches, mut report) = std::mem::take(&mut *search.report.lock().unwrap());
+    matches.sort_unstable();
+    if config.mode == SearchMode::First {
+        matches.truncate(1);
+    }
+    report.assignments = matches
+        .into_iter()
+        .map(|candidate| search.assignment(candidate))
+        .collect();
+
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    fn noun_verb_patches() -> Vec<Patch> {
+        vec![Patch::new(1, 0..=99), Patch::new(2, 0..=99)]
+    }
+
+    #[test]
+    fn values_of_the_widest_ranges_are_exact() {
+        let full = Patch::new(0, isize::MIN..=isize::MAX);
+        assert_eq!(usize::MAX, full.len());
+        assert_eq!(isize::MIN, full.value(0));
+        assert_eq!(-1, full.value(isize::MAX as usize));
+        assert_eq!(isize::MAX - 1, full.value(full.len() - 1));
+
+        let wide = Patch::new(0, -5..=isize::MAX);
+        assert_eq!(isize::MAX as usize + 6, wide.len());
+        assert_eq!(0, wide.value(5));
+        assert_eq!(isize::MAX, wide.value(wide.len() - 1));
+    }
+
+    #[test]
+    fn finds_day2_noun_and_verb() {
+        let tape = Tape::new(utils::read_input_file("day2.input"));
+        let report = search(
+            &tape,
+            &noun_verb_patches(),
+            |outcome| outcome.memory[0] == 19_690_720,
+            &SearchConfig {
+                spec: Spec::Day2,
+                ..SearchConfig::default()
+            },
+        );
+
+        let assignment = &report.assignments[0];
+        assert_eq!(
+            6635,
+            100 * assignment.value(1).unwrap() + assignment.value(2).unwrap()
+        );
+    }
+
+    #[test]
+    fn finds_all_assignments_including_range_ends() {
+        // stores noun + verb at 0
+        let tape = Tape::new(vec![1101, 0, 0, 0, 99]);
+        let config = SearchConfig {
+            mode: SearchMode::All,
+            ..SearchConfig::default()
+        };
+        let report = search(
+            &tape,
+            &noun_verb_patches(),
+            |outcome| outcome.memory[0] == 197,
+            &config,
+        );
+
+        let pairs: Vec<_> = report
+            .assignments
+            .iter()
+            .map(|assignment| (assignment.value(1).unwrap(), assignment.value(2).unwrap()))
+            .collect();
+        assert_eq!(vec![(98, 99), (99, 98)], pairs);
+        assert_eq!(100 * 100, report.evaluated);
+    }
+
+    #[test]
+    fn first_assignment_does_not_depend_on_number_of_workers() {
+        let tape = Tape::new(vec![1101, 0, 0, 0, 99]);
+        let target = |outcome: &Outcome| outcome.memory[0] == 100;
+
+        let single = search(
+            &tape,
+            &noun_verb_patches(),
+            target,
+            &SearchConfig {
+                workers: 1,
+                ..SearchConfig::default()
+            },
+        );
+        let many = search(
+            &tape,
+            &noun_verb_patches(),
+            target,
+            &SearchConfig {
+                workers: 8,
+                ..SearchConfig::default()
+            },
+        );
+
+        assert_eq!(vec![(1, 1), (2, 99)], single.assignments[0].values);
+        assert_eq!(single.assignments, many.assignments);
+    }
+
+    #[test]
+    fn outputs_can_be_targeted_and_failing_candidates_are_counted() {
+        // reads a value and prints it multiplied by the patched immediate at 4
+        let tape = Tape::new(vec![3, 9, 1002, 9, 0, 9, 4, 9, 99, 0, 0]);
+        let patches = vec![Patch::new(4, -3..=3)];
+        let config = SearchConfig {
+            inputs: vec![7],
+            mode: SearchMode::All,
+            ..SearchConfig::default()
+        };
+
+        let report = search(&tape, &patches, |outcome| outcome.outputs == [21], &config);
+        assert_eq!(vec![(4, 3)], report.assignments[0].values);
+        assert_eq!(7, report.evaluated);
+
+        // loops forever unless the patched jump condition is 0
+        let looping = Tape::new(vec![1105, 1, 0, 99]);
+        let config = SearchConfig {
+            budget: 50,
+            ..config
+        };
+        let report = search(&looping, &[Patch::new(1, 0..=1)], |_| true, &config);
+        assert_eq!(vec![(1, 0)], report.assignments[0].values);
+        assert_eq!(1, report.budget_exhausted);
+    }
+}
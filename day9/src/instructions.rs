@@ -0,0 +1,583 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::intcode_machine::{
+    AccessKind, InputError, MachineInput, MachineOutput, MemoryAccess, ParamMode, Tape, TapeError,
+    ADD_OP_CODE, EQUALS_OP_CODE, HALT_OP_CODE, INPUT_OP_CODE, JMP_FALSE_OP_CODE, JMP_TRUE_OP_CODE,
+    LESS_THAN_OP_CODE, MUL_OP_CODE, OUTPUT_OP_CODE, RLT_BASE_OFFSET_OP_CODE,
+};
+
+const OP_CODE_DIGITS: isize = 100;
+const PARAM_MODE_BASE: isize = 10;
+
+// Rules for parameter modes. Every parameter can be given in any mode,
+// apart from the ones that are written to - those can never be immediate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParamKind {
+    Read,
+    Write,
+}
+
+// What should happen with the head once the instruction got executed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Flow {
+    // move to the instruction right after the current one
+    Continue,
+    Jump(usize),
+    // leave the head in place so that the instruction gets retried
+    AwaitInput,
+    Halt,
+}
+
+#[derive(Debug)]
+pub enum InstructionError {
+    TapeError,
+    ExecutionFailure,
+    InputError(InputError),
+}
+
+impl From<TapeError> for InstructionError {
+    fn from(_: TapeError) -> Self {
+        InstructionError::TapeError
+    }
+}
+
+#[derive(Debug)]
+pub enum InstructionSetError {
+    InvalidOpCode(isize),
+    AlreadyRegistered(isize),
+}
+
+// Everything an instruction is allowed to touch during its execution
+pub struct Operands<'a> {
+    pub(crate) tape: &'a mut Tape,
+    pub(crate) head_position: usize,
+    pub(crate) relative_base: &'a mut isize,
+    pub(crate) param_modes: &'a [ParamMode],
+    pub(crate) input: &'a mut dyn MachineInput,
+    pub(crate) output: &'a mut dyn MachineOutput,
+    pub(crate) trace: Option<&'a mut Vec<MemoryAccess>>,
+}
+
+impl<'a> Operands<'a> {
+    fn param_position(&self, param: usize) -> Result<usize, InstructionError> {
+        if param >= self.param_modes.len() {
+            // instruction tried to use more parameters than it has declared
+            return Err(InstructionError::ExecutionFailure);
+        }
+        Ok(self.head_position + 1 + param)
+    }
+
+    // memory address the parameter refers to, unless it is an immediate value
+    fn address(&mut self, param: usize) -> Option<usize> {
+        let literal_value = self.tape.read(self.head_position + 1 + param);
+        let address = match self.param_modes[param] {
+            ParamMode::Position => Some(literal_value),
+            ParamMode::Relative => literal_value.checked_add(*self.relative_base),
+            ParamMode::Immediate => return None,
+        };
+        match address {
+            Some(address) if address >= 0 => Some(address as usize),
+            _ => None,
+        }
+    }
+
+    fn record(&mut self, kind: AccessKind, address: usize) {
+        if let Some(trace) = self.trace.as_mut() {
+            trace.push(MemoryAccess {
+                kind,
+                address,
+                instruction: self.head_position,
+            });
+        }
+    }
+
+    // value of the parameter, resolved according to its mode
+    pub fn read(&mut self, param: usize) -> Result<isize, InstructionError> {
+        let position = self.param_position(param)?;
+        let value = self
+            .tape
+            .mode_read(position, *self.relative_base, self.param_modes[param])?;
+
+        if self.trace.is_some() {
+            if let Some(address) = self.address(param) {
+                self.record(AccessKind::Read, address);
+            }
+        }
+        Ok(value)
+    }
+
+    // stores the value at the address pointed to by the parameter
+    pub fn write(&mut self, param: usize, value: isize) -> Result<(), InstructionError> {
+        let position = self.param_position(param)?;
+        let previous = match self.address(param) {
+            Some(address) if self.trace.is_some() => Some((address, self.tape.read(address))),
+            _ => None,
+        };
+
+        let literal_value = self.tape.read(position);
+        self.tape.mode_write(
+            literal_value,
+            *self.relative_base,
+            self.param_modes[param],
+            value,
+        )?;
+
+        if let Some((address, previous)) = previous {
+            self.record(AccessKind::Write { previous, value }, address);
+        }
+        Ok(())
+    }
+
+    pub fn tape(&mut self) -> &mut Tape {
+        self.tape
+    }
+
+    pub fn head_position(&self) -> usize {
+        self.head_position
+    }
+
+    pub fn relative_base(&self) -> isize {
+        *self.relative_base
+    }
+
+    pub fn adjust_relative_base(&mut self, offset: isize) -> Result<(), InstructionError> {
+        *self.relative_base = self
+            .relative_base
+            .checked_add(offset)
+            .ok_or(InstructionError::ExecutionFailure)?;
+        Ok(())
+    }
+
+    // None if the input ran out of values for now, so that the instruction can be retried later
+    pub fn read_input(&mut self) -> Result<Option<isize>, InstructionError> {
+        match (self.input.read_value(), self.input.take_error()) {
+            (Some(value), _) => Ok(Some(value)),
+            (None, Some(err)) => Err(InstructionError::InputError(err)),
+            (None, None) => Ok(None),
+        }
+    }
+
+    pub fn write_output(&mut self, value: isize) {
+        self.output.write_value(value)
+    }
+}
+
+type Execute = dyn Fn(&mut Operands) -> Result<Flow, InstructionError> + Send + Sync;
+
+#[derive(Clone)]
+pub struct Instruction {
+    name: &'static str,
+    params: Vec<ParamKind>,
+    execute: Arc<Execute>,
+}
+
+impl Instruction {
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub fn params(&self) -> &[ParamKind] {
+        &self.params
+    }
+
+    pub(crate) fn execute(&self, operands: &mut Operands) -> Result<Flow, InstructionError> {
+        (self.execute)(operands)
+    }
+}
+
+impl fmt::Debug for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{:?}", self.name, self.params)
+    }
+}
+
+// instruction found at the head together with the modes of its parameters
+#[derive(Debug)]
+pub(crate) struct Decoded<'a> {
+    pub(crate) op_code: isize,
+    pub(crate) instruction: &'a Instruction,
+    pub(crate) param_modes: Vec<ParamMode>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct InstructionSet {
+    instructions: HashMap<isize, Instruction>,
+}
+
+impl InstructionSet {
+    pub fn empty() -> Self {
+        Default::default()
+    }
+
+    // all of the instructions up to and including day9
+    pub fn standard() -> Self {
+        use ParamKind::*;
+
+        let mut set = InstructionSet::empty();
+        let mut add = |op_code, name, params, execute| {
+            set.instructions.insert(
+                op_code,
+                Instruction {
+                    name,
+                    params,
+                    execute,
+                },
+            );
+        };
+
+        add(
+            ADD_OP_CODE,
+            "add",
+            vec![Read, Read, Write],
+            Arc::new(|ops: &mut Operands| {
+                let result = ops.read(0)? + ops.read(1)?;
+                ops.write(2, result)?;
+                Ok(Flow::Continue)
+            }),
+        );
+        add(
+            MUL_OP_CODE,
+            "mul",
+            vec![Read, Read, Write],
+            Arc::new(|ops: &mut Operands| {
+                let result = ops.read(0)? * ops.read(1)?;
+                ops.write(2, result)?;
+                Ok(Flow::Continue)
+            }),
+        );
+        add(
+            INPUT_OP_CODE,
+            "in",
+            vec![Write],
+            Arc::new(|ops: &mut Operands| match ops.read_input()? {
+                Some(value) => {
+                    ops.write(0, value)?;
+                    Ok(Flow::Continue)
+                }
+                None => Ok(Flow::AwaitInput),
+            }),
+        );
+        add(
+            OUTPUT_OP_CODE,
+            "out",
+            vec![Read],
+            Arc::new(|ops: &mut Operands| {
+                let value = ops.read(0)?;
+                ops.write_output(value);
+                Ok(Flow::Continue)
+            }),
+        );
+        add(
+            JMP_TRUE_OP_CODE,
+            "jt",
+            vec![Read, Read],
+            Arc::new(|ops: &mut Operands| {
+                // target is resolved even if the jump is not taken
+                let (param, target) = (ops.read(0)?, ops.read(1)?);
+                if param != 0 {
+                    Ok(Flow::Jump(target as usize))
+                } else {
+                    Ok(Flow::Continue)
+                }
+            }),
+        );
+        add(
+            JMP_FALSE_OP_CODE,
+            "jf",
+            vec![Read, Read],
+            Arc::new(|ops: &mut Operands| {
+                // target is resolved even if the jump is not taken
+                let (param, target) = (ops.read(0)?, ops.read(1)?);
+                if param == 0 {
+                    Ok(Flow::Jump(target as usize))
+                } else {
+                    Ok(Flow::Continue)
+                }
+            }),
+        );
+        add(
+            LESS_THAN_OP_CODE,
+            "lt",
+            vec![Read, Read, Write],
+            Arc::new(|ops: &mut Operands| {
+                let result = ops.read(0)? < ops.read(1)?;
+                ops.write(2, result as isize)?;
+                Ok(Flow::Continue)
+            }),
+        );
+        add(
+            EQUALS_OP_CODE,
+            "eq",
+            vec![Read, Read, Write],
+            Arc::new(|ops: &mut Operands| {
+                let result = ops.read(0)? == ops.read(1)?;
+                ops.write(2, result as isize)?;
+                Ok(Flow::Continue)
+            }),
+        );
+        add(
+            RLT_BASE_OFFSET_OP_CODE,
+            "rbo",
+            vec![Read],
+            Arc::new(|ops: &mut Operands| {
+                let offset = ops.read(0)?;
+                ops.adjust_relative_base(offset)?;
+                Ok(Flow::Continue)
+            }),
+        );
+        add(
+            HALT_OP_CODE,
+            "halt",
+            vec![],
+            Arc::new(|_: &mut Operands| Ok(Flow::Halt)),
+        );
+
+        set
+    }
+
+    pub fn register<F>(
+        &mut self,
+        op_code: isize,
+        name: &'static str,
+        params: Vec<ParamKind>,
+        execute: F,
+    ) -> Result<(), InstructionSetError>
+    where
+        F: Fn(&mut Operands) -> Result<Flow, InstructionError> + Send + Sync + 'static,
+    {
+        if op_code <= 0 || op_code >= OP_CODE_DIGITS {
+            return Err(InstructionSetError::InvalidOpCode(op_code));
+        }
+        if self.instructions.contains_key(&op_code) {
+            return Err(InstructionSetError::AlreadyRegistered(op_code));
+        }
+
+        let instruction = Instruction {
+            name,
+            params,
+            execute: Arc::new(execute),
+        };
+        self.instructions.insert(op_code, instruction);
+        Ok(())
+    }
+
+    pub fn get(&self, op_code: isize) -> Option<&Instruction> {
+        self.instructions.get(&op_code)
+    }
+
+    // None if the code does not correspond to any known instruction or has invalid modes
+    pub(crate) fn decode(&self, code: isize) -> Option<Decoded<'_>> {
+        if code < 0 {
+            return None;
+        }
+
+        let op_code = code % OP_CODE_DIGITS;
+        let instruction = self.instructions.get(&op_code)?;
+
+        let mut modes = code / OP_CODE_DIGITS;
+        let mut param_modes = Vec::with_capacity(instruction.params.len());
+        for &kind in instruction.params.iter() {
+            let mode = ParamMode::try_from((modes % PARAM_MODE_BASE) as usize).ok()?;
+            if kind == ParamKind::Write && mode == ParamMode::Immediate {
+                return None;
+            }
+            param_modes.push(mode);
+            modes /= PARAM_MODE_BASE;
+        }
+
+        Some(Decoded {
+            op_code,
+            instruction,
+            param_modes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode_machine::{IntcodeMachine, IntcodeMachineError, Spec, ValueQueue};
+    use std::sync::Mutex;
+
+    const MOD_OP_CODE: isize = 10;
+    const DIV_OP_CODE: isize = 11;
+    const DEBUG_OP_CODE: isize = 12;
+
+    fn arithmetic_set() -> InstructionSet {
+        let mut set = InstructionSet::standard();
+        set.register(
+            MOD_OP_CODE,
+            "mod",
+            vec![ParamKind::Read, ParamKind::Read, ParamKind::Write],
+            |ops: &mut Operands| {
+                let divisor = ops.read(1)?;
+                if divisor == 0 {
+                    return Err(InstructionError::ExecutionFailure);
+                }
+                let result = ops.read(0)? % divisor;
+                ops.write(2, result)?;
+                Ok(Flow::Continue)
+            },
+        )
+        .unwrap();
+        set.register(
+            DIV_OP_CODE,
+            "div",
+            vec![ParamKind::Read, ParamKind::Read, ParamKind::Write],
+            |ops: &mut Operands| {
+                let divisor = ops.read(1)?;
+                if divisor == 0 {
+                    return Err(InstructionError::ExecutionFailure);
+                }
+                let result = ops.read(0)? / divisor;
+                ops.write(2, result)?;
+                Ok(Flow::Continue)
+            },
+        )
+        .unwrap();
+        set
+    }
+
+    fn run_with_set(
+        tape: Vec<isize>,
+        set: InstructionSet,
+    ) -> Result<Vec<isize>, IntcodeMachineError> {
+        let mut machine =
+            IntcodeMachine::new(Tape::new(tape), ValueQueue::new(), ValueQueue::new());
+        machine.set_instruction_set(set);
+        machine.run()?;
+        Ok(machine.output_mut().drain())
+    }
+
+    #[test]
+    fn modes_are_decoded_from_most_significant_digits() {
+        let set = InstructionSet::standard();
+        let decoded = set.decode(21_101).unwrap();
+
+        assert_eq!(ADD_OP_CODE, decoded.op_code);
+        assert_eq!("add", decoded.instruction.name());
+        assert_eq!(
+            vec![
+                ParamMode::Immediate,
+                ParamMode::Immediate,
+                ParamMode::Relative
+            ],
+            decoded.param_modes
+        );
+    }
+
+    #[test]
+    fn invalid_codes_are_not_decoded() {
+        let set = InstructionSet::standard();
+
+        assert!(set.decode(-1).is_none());
+        assert!(set.decode(42).is_none());
+        // mode 3 does not exist
+        assert!(set.decode(301).is_none());
+        // write in immediate mode
+        assert!(set.decode(11_101).is_none());
+    }
+
+    #[test]
+    fn only_unused_op_codes_can_be_registered() {
+        let mut set = InstructionSet::standard();
+        let noop = |_: &mut Operands| Ok(Flow::Continue);
+
+        match set.register(ADD_OP_CODE, "noop", vec![], noop) {
+            Err(InstructionSetError::AlreadyRegistered(ADD_OP_CODE)) => (),
+            other => panic!("expected already registered error, got {:?}", other),
+        }
+        match set.register(100, "noop", vec![], noop) {
+            Err(InstructionSetError::InvalidOpCode(100)) => (),
+            other => panic!("expected invalid op code error, got {:?}", other),
+        }
+        assert!(set.register(10, "noop", vec![], noop).is_ok());
+        assert_eq!("noop", set.get(10).unwrap().name());
+    }
+
+    #[test]
+    fn custom_instructions_are_executed_with_all_parameter_modes() {
+        // 17 mod 5, 17 div 5 stored relative to base 20, then both are printed
+        let tape = vec![109, 20, 1110, 17, 5, 0, 21111, 17, 5, 1, 4, 0, 204, 1, 99];
+
+        assert_eq!(vec![2, 3], run_with_set(tape, arithmetic_set()).unwrap());
+    }
+
+    #[test]
+    fn failing_custom_instruction_stops_the_machine() {
+        let tape = vec![11_110, 1, 0, 0, 99];
+
+        match run_with_set(tape, arithmetic_set()) {
+            Err(IntcodeMachineError::ExecutionFailure) => (),
+            other => panic!("expected execution failure, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn custom_instructions_can_inspect_machine_state() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut set = InstructionSet::standard();
+        let debug_log = Arc::clone(&log);
+        set.register(DEBUG_OP_CODE, "debug", vec![ParamKind::Read], move |ops| {
+            let value = ops.read(0)?;
+            debug_log.lock().unwrap().push(format!(
+                "head: {}, base: {}, value: {}",
+                ops.head_position(),
+                ops.relative_base(),
+                value
+            ));
+            Ok(Flow::Continue)
+        })
+        .unwrap();
+
+        let tape = vec![109, 3, 112, 7, 212, -1, 99];
+        assert!(run_with_set(tape, set).unwrap().is_empty());
+        assert_eq!(
+            vec![
+                "head: 2, base: 3, value: 7".to_string(),
+                "head: 4, base: 3, value: 112".to_string()
+            ],
+            *log.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn custom_instructions_are_only_part_of_day9_spec() {
+        let tape = vec![1110, 17, 5, 0, 99];
+        let mut machine = IntcodeMachine::new(
+            Tape::new(tape.clone()),
+            ValueQueue::new(),
+            ValueQueue::new(),
+        );
+        machine.set_instruction_set(arithmetic_set());
+        machine.set_spec(Spec::Day5);
+
+        match machine.step() {
+            Err(IntcodeMachineError::UnsupportedInstruction(1110)) => (),
+            other => panic!("expected unsupported instruction, got {:?}", other),
+        }
+        assert_eq!(&tape[..], machine.dump_state().tape().as_slice());
+    }
+
+    #[test]
+    fn custom_instructions_survive_dumping_and_loading_state() {
+        // waits for input before dividing 17 by 5
+        let tape = vec![3, 5, 1110, 17, 5, 0, 99];
+        let mut machine =
+            IntcodeMachine::new(Tape::new(tape), ValueQueue::new(), ValueQueue::new());
+        machine.set_instruction_set(arithmetic_set());
+
+        let state = match machine.run() {
+            Err(IntcodeMachineError::InputFailure(state)) => state,
+            other => panic!("expected input failure, got {:?}", other),
+        };
+        assert!(state.instruction_set().get(10).is_some());
+
+        let mut machine =
+            IntcodeMachine::load_state(state, ValueQueue::from(vec![5]), ValueQueue::new());
+        assert_eq!(3, machine.run().unwrap());
+    }
+}
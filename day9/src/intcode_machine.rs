@@ -1,25 +1,28 @@
+use std::collections::VecDeque;
 use std::convert::TryFrom;
-use std::io::{BufRead, Write};
-
-use crate::utils;
-
-const ADD_OP_CODE: isize = 1;
-const MUL_OP_CODE: isize = 2;
-const INPUT_OP_CODE: isize = 3;
-const OUTPUT_OP_CODE: isize = 4;
-const JMP_TRUE_OP_CODE: isize = 5;
-const JMP_FALSE_OP_CODE: isize = 6;
-const LESS_THAN_OP_CODE: isize = 7;
-const EQUALS_OP_CODE: isize = 8;
-const RLT_BASE_OFFSET_OP_CODE: isize = 9;
-const HALT_OP_CODE: isize = 99;
-
-const POSITION_MODE: usize = 0;
-const IMMEDIATE_MODE: usize = 1;
-const RELATIVE_MODE: usize = 2;
+use std::fmt;
+use std::io::{self, BufRead, Write};
+use std::sync::{Arc, OnceLock};
+
+use crate::instructions::{Decoded, Flow, InstructionError, InstructionSet, Operands};
+
+pub const ADD_OP_CODE: isize = 1;
+pub const MUL_OP_CODE: isize = 2;
+pub const INPUT_OP_CODE: isize = 3;
+pub const OUTPUT_OP_CODE: isize = 4;
+pub const JMP_TRUE_OP_CODE: isize = 5;
+pub const JMP_FALSE_OP_CODE: isize = 6;
+pub const LESS_THAN_OP_CODE: isize = 7;
+pub const EQUALS_OP_CODE: isize = 8;
+pub const RLT_BASE_OFFSET_OP_CODE: isize = 9;
+pub const HALT_OP_CODE: isize = 99;
+
+pub const POSITION_MODE: usize = 0;
+pub const IMMEDIATE_MODE: usize = 1;
+pub const RELATIVE_MODE: usize = 2;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
-enum ParamMode {
+pub(crate) enum ParamMode {
     Position,
     Immediate,
     Relative,
@@ -42,128 +45,253 @@ impl TryFrom<usize> for ParamMode {
     }
 }
 
-type HeadPositionUpdate = usize;
+pub trait MachineInput {
+    // None signals that no further input is (currently) available
+    fn read_value(&mut self) -> Option<isize>;
+
+    // reason behind the last None, if the input failed rather than just ran out of values
+    fn take_error(&mut self) -> Option<InputError> {
+        None
+    }
+}
+
+pub trait MachineOutput {
+    fn write_value(&mut self, value: isize);
+}
 
 #[derive(Debug)]
-enum OpCodeExecutionError {
-    TapeError,
-    InvalidOpArguments,
-    ExecutionFailure,
-    ExecutionFinished,
-    InputFailure,
+pub enum InputError {
+    EndOfInput,
+    IoError(io::Error),
+    InvalidValue { line: usize, token: String },
 }
 
-impl From<TapeError> for OpCodeExecutionError {
-    fn from(_: TapeError) -> Self {
-        OpCodeExecutionError::TapeError
+impl From<io::Error> for InputError {
+    fn from(err: io::Error) -> Self {
+        InputError::IoError(err)
     }
 }
 
-#[derive(Debug)]
-enum OpCode {
-    Add(Vec<ParamMode>),
-    Mul(Vec<ParamMode>),
-    In(Vec<ParamMode>),
-    Out(Vec<ParamMode>),
-    Jt(Vec<ParamMode>),
-    Jf(Vec<ParamMode>),
-    Lt(Vec<ParamMode>),
-    Eq(Vec<ParamMode>),
-    Rbo(Vec<ParamMode>),
-    Halt,
-    Er(isize),
-}
-
-impl From<isize> for OpCode {
-    fn from(code: isize) -> Self {
-        use OpCode::*;
-
-        // make sure the opcode itself is positive, otherwise we have an invalid execution
-        if code < 0 {
-            return Er(code);
-        }
-
-        let digits = utils::num_to_digits_vec(code as usize);
-
-        let mut opcode_digits: Vec<_> = std::iter::repeat(0)
-            .chain(digits.clone().into_iter())
-            .rev()
-            .take(2)
-            .collect();
-        opcode_digits.reverse();
-        let op_code_value = utils::digits_vec_to_num(&opcode_digits);
-
-        let num_args = match op_code_value as isize {
-            ADD_OP_CODE => 3,
-            MUL_OP_CODE => 3,
-            JMP_TRUE_OP_CODE => 2,
-            JMP_FALSE_OP_CODE => 2,
-            LESS_THAN_OP_CODE => 3,
-            EQUALS_OP_CODE => 3,
-            INPUT_OP_CODE => 1,
-            OUTPUT_OP_CODE => 1,
-            RLT_BASE_OFFSET_OP_CODE => 1,
-            HALT_OP_CODE => 0,
-            _ => 0,
-        };
+impl fmt::Display for InputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputError::EndOfInput => write!(f, "end of input"),
+            InputError::IoError(err) => write!(f, "failed to read input: {}", err),
+            InputError::InvalidValue { line, token } => {
+                write!(f, "line {}: '{}' is not a valid value", line, token)
+            }
+        }
+    }
+}
+
+// Values given as lines of text, any number of them per line separated by commas or
+// whitespace. If a prompt is set, it gets written out whenever a new line has to be read.
+// A line with a malformed value is discarded as a whole, so that it can be simply retyped.
+pub struct LineInput<R> {
+    reader: R,
+    pending: VecDeque<isize>,
+    lines_read: usize,
+    prompt: Option<(String, Box<dyn Write + Send>)>,
+    error: Option<InputError>,
+}
+
+impl<R: BufRead> LineInput<R> {
+    pub fn new(reader: R) -> Self {
+        LineInput {
+            reader,
+            pending: VecDeque::new(),
+            lines_read: 0,
+            prompt: None,
+            error: None,
+        }
+    }
+
+    pub fn with_prompt<W: Write + Send + 'static>(mut self, prompt: &str, writer: W) -> Self {
+        self.prompt = Some((prompt.to_string(), Box::new(writer)));
+        self
+    }
+
+    fn read_line(&mut self) -> Result<(), InputError> {
+        if let Some((prompt, writer)) = self.prompt.as_mut() {
+            write!(writer, "{}", prompt)?;
+            writer.flush()?;
+        }
 
-        let param_modes_vec: Vec<_> = std::iter::repeat(0)
-            .chain(digits.into_iter())
-            .rev()
-            .skip(2)
-            .take(num_args)
-            .map(|x| ParamMode::try_from(x).unwrap())
-            .collect();
+        let mut buffer = String::new();
+        if self.reader.read_line(&mut buffer)? == 0 {
+            return Err(InputError::EndOfInput);
+        }
+        self.lines_read += 1;
+
+        let values = buffer
+            .split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                token
+                    .parse::<isize>()
+                    .map_err(|_| InputError::InvalidValue {
+                        line: self.lines_read,
+                        token: token.to_string(),
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        self.pending.extend(values);
+        Ok(())
+    }
+}
 
-        match op_code_value as isize {
-            ADD_OP_CODE => Add(param_modes_vec),
-            MUL_OP_CODE => Mul(param_modes_vec),
-            JMP_TRUE_OP_CODE => Jt(param_modes_vec),
-            JMP_FALSE_OP_CODE => Jf(param_modes_vec),
-            LESS_THAN_OP_CODE => Lt(param_modes_vec),
-            EQUALS_OP_CODE => Eq(param_modes_vec),
-            INPUT_OP_CODE => In(param_modes_vec),
-            OUTPUT_OP_CODE => Out(param_modes_vec),
-            RLT_BASE_OFFSET_OP_CODE => Rbo(param_modes_vec),
-            HALT_OP_CODE => Halt,
-            _ => Er(code),
+impl<R: BufRead> MachineInput for LineInput<R> {
+    fn read_value(&mut self) -> Option<isize> {
+        // blank lines don't provide any values, so keep on reading
+        while self.pending.is_empty() {
+            if let Err(err) = self.read_line() {
+                self.error = Some(err);
+                return None;
+            }
         }
+        self.pending.pop_front()
+    }
+
+    fn take_error(&mut self) -> Option<InputError> {
+        self.error.take()
+    }
+}
+
+impl<W: Write> MachineOutput for W {
+    fn write_value(&mut self, value: isize) {
+        writeln!(self, "{}", value).unwrap();
+    }
+}
+
+// value-level I/O that does not have to go through text representation at all
+#[derive(Debug, Clone, Default)]
+pub struct ValueQueue(VecDeque<isize>);
+
+impl ValueQueue {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn push(&mut self, value: isize) {
+        self.0.push_back(value)
+    }
+
+    pub fn pop(&mut self) -> Option<isize> {
+        self.0.pop_front()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn drain(&mut self) -> Vec<isize> {
+        self.0.drain(..).collect()
+    }
+}
+
+impl From<Vec<isize>> for ValueQueue {
+    fn from(values: Vec<isize>) -> Self {
+        ValueQueue(values.into())
+    }
+}
+
+impl MachineInput for ValueQueue {
+    fn read_value(&mut self) -> Option<isize> {
+        self.pop()
+    }
+}
+
+impl MachineOutput for ValueQueue {
+    fn write_value(&mut self, value: isize) {
+        self.push(value)
+    }
+}
+
+// Instruction set and memory semantics as they were defined by the given day's puzzle
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Spec {
+    // add, mul and halt only, all parameters are addresses
+    Day2,
+    // I/O, jumps, comparisons and immediate mode
+    Day5,
+    // relative base and memory beyond the initial program
+    #[default]
+    Day9,
+}
+
+impl Spec {
+    // custom instructions were never part of any puzzle, so only the latest spec allows them
+    fn supports(self, op_code: isize, param_modes: &[ParamMode]) -> bool {
+        let supports_mode = |mode: &ParamMode| match self {
+            Spec::Day2 => *mode == ParamMode::Position,
+            Spec::Day5 => *mode != ParamMode::Relative,
+            Spec::Day9 => true,
+        };
+        let supports_op = match self {
+            Spec::Day2 => matches!(op_code, ADD_OP_CODE | MUL_OP_CODE | HALT_OP_CODE),
+            Spec::Day5 => matches!(op_code, ADD_OP_CODE..=EQUALS_OP_CODE | HALT_OP_CODE),
+            Spec::Day9 => true,
+        };
+
+        supports_op && param_modes.iter().all(supports_mode)
+    }
+
+    fn has_unbounded_memory(self) -> bool {
+        self == Spec::Day9
     }
 }
 
 #[derive(Debug)]
-enum TapeError {
+#[allow(clippy::enum_variant_names)]
+pub(crate) enum TapeError {
     WriteOutOfRangeError,
     ReadOutOfRangeError,
     WriteInImmediateModeError,
 }
 
+// Cells are shared between clones of the tape until one of them gets modified, so that
+// snapshots and forked machines don't copy the memory unless they actually write to it.
+// Sharing is all or nothing: the first write to a shared tape copies it whole, no matter how
+// few cells change, which keeps the cells contiguous for `as_slice`.
 #[derive(Debug, Clone)]
-pub struct Tape(Vec<isize>);
+pub struct Tape(Arc<Vec<isize>>);
 
 impl Tape {
-    pub(crate) fn new(input: Vec<isize>) -> Self {
-        Tape(input)
+    pub fn new(input: Vec<isize>) -> Self {
+        Tape(Arc::new(input))
+    }
+
+    pub fn as_slice(&self) -> &[isize] {
+        &self.0
+    }
+
+    // whether both tapes still share the same cells
+    pub fn shares_memory_with(&self, other: &Tape) -> bool {
+        Arc::ptr_eq(&self.0, &other.0)
     }
 
     fn resize(&mut self, lower_bound: usize) {
-        self.0.resize(lower_bound, 0);
+        Arc::make_mut(&mut self.0).resize(lower_bound, 0);
     }
 
     fn len(&self) -> usize {
         self.0.len()
     }
 
-    fn write(&mut self, position: usize, value: isize) {
+    pub fn write(&mut self, position: usize, value: isize) {
         if position >= self.0.len() {
             // according to day9 specs, write should always succeed (unless to negative index)
             self.resize(position + 1);
         }
 
-        self.0[position] = value;
+        Arc::make_mut(&mut self.0)[position] = value;
     }
 
-    fn read(&mut self, position: usize) -> isize {
+    pub(crate) fn read(&mut self, position: usize) -> isize {
         if position >= self.len() {
             // according to day9 specs, read should always succeed (unless on negative index)
             self.resize(position + 1);
@@ -172,7 +300,7 @@ impl Tape {
         self.0[position]
     }
 
-    fn mode_read(
+    pub(crate) fn mode_read(
         &mut self,
         position: usize,
         relative_base: isize,
@@ -187,48 +315,56 @@ impl Tape {
                     Ok(self.read(literal_value as usize))
                 }
             }
-            ParamMode::Relative => {
-                if (literal_value + relative_base) < 0 {
-                    Err(TapeError::ReadOutOfRangeError)
-                } else {
-                    Ok(self.read((literal_value + relative_base) as usize))
-                }
-            }
+            ParamMode::Relative => match literal_value.checked_add(relative_base) {
+                Some(address) if address >= 0 => Ok(self.read(address as usize)),
+                _ => Err(TapeError::ReadOutOfRangeError),
+            },
 
             ParamMode::Immediate => Ok(literal_value),
         }
     }
 
-    fn mode_write(
+    pub(crate) fn mode_write(
         &mut self,
-        position: usize,
+        literal_value: isize,
         relative_base: isize,
         param_mode: ParamMode,
         value: isize,
     ) -> Result<(), TapeError> {
         match param_mode {
             ParamMode::Position => {
-                self.write(position, value);
-                Ok(())
-            }
-            ParamMode::Relative => {
-                if position as isize + relative_base < 0 {
+                if literal_value < 0 {
                     return Err(TapeError::WriteOutOfRangeError);
                 }
-                self.write((position as isize + relative_base) as usize, value);
+                self.write(literal_value as usize, value);
                 Ok(())
             }
+            ParamMode::Relative => match literal_value.checked_add(relative_base) {
+                Some(address) if address >= 0 => {
+                    self.write(address as usize, value);
+                    Ok(())
+                }
+                _ => Err(TapeError::WriteOutOfRangeError),
+            },
 
             ParamMode::Immediate => Err(TapeError::WriteInImmediateModeError),
         }
     }
 }
 
+// the standard set is shared between all of the machines unless replaced with a custom one
+fn standard_instructions() -> Arc<InstructionSet> {
+    static STANDARD: OnceLock<Arc<InstructionSet>> = OnceLock::new();
+    Arc::clone(STANDARD.get_or_init(|| Arc::new(InstructionSet::standard())))
+}
+
 #[derive(Debug)]
 pub enum IntcodeMachineError {
     TapeOutOfBoundsError,
     ExecutionFailure,
     InputFailure(State),
+    InputError(InputError),
+    UnsupportedInstruction(isize),
 }
 
 impl From<TapeError> for IntcodeMachineError {
@@ -237,17 +373,45 @@ impl From<TapeError> for IntcodeMachineError {
     }
 }
 
-impl From<OpCodeExecutionError> for IntcodeMachineError {
-    fn from(_: OpCodeExecutionError) -> Self {
-        IntcodeMachineError::ExecutionFailure
+impl From<InstructionError> for IntcodeMachineError {
+    fn from(err: InstructionError) -> Self {
+        match err {
+            InstructionError::InputError(err) => IntcodeMachineError::InputError(err),
+            _ => IntcodeMachineError::ExecutionFailure,
+        }
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AccessKind {
+    // cell is part of an executed instruction, either its opcode or one of parameters
+    Execute,
+    Read,
+    Write { previous: isize, value: isize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MemoryAccess {
+    pub kind: AccessKind,
+    pub address: usize,
+    // address of the instruction that caused the access
+    pub instruction: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepOutcome {
+    Executed,
+    AwaitingInput,
+    Halted,
+}
+
 #[derive(Debug, Clone)]
 pub struct State {
     tape: Tape,
     relative_base: isize,
     head_position: usize,
+    spec: Spec,
+    instructions: Arc<InstructionSet>,
 }
 
 impl State {
@@ -256,28 +420,56 @@ impl State {
             tape,
             relative_base: 0,
             head_position: 0,
+            spec: Spec::default(),
+            instructions: standard_instructions(),
         }
     }
+
+    pub fn tape(&self) -> &Tape {
+        &self.tape
+    }
+
+    pub fn relative_base(&self) -> isize {
+        self.relative_base
+    }
+
+    pub fn head_position(&self) -> usize {
+        self.head_position
+    }
+
+    pub fn spec(&self) -> Spec {
+        self.spec
+    }
+
+    pub fn instruction_set(&self) -> &InstructionSet {
+        &self.instructions
+    }
 }
 
 impl Default for State {
     fn default() -> Self {
         State {
-            tape: Tape(Vec::new()),
+            tape: Tape::new(Vec::new()),
             relative_base: 0,
             head_position: 0,
+            spec: Spec::default(),
+            instructions: standard_instructions(),
         }
     }
 }
 
 pub struct IntcodeMachine<R, W>
 where
-    R: BufRead,
-    W: Write,
+    R: MachineInput,
+    W: MachineOutput,
 {
     tape: Tape,
     head_position: usize,
     relative_base: isize,
+    spec: Spec,
+    instructions: Arc<InstructionSet>,
+    // only recorded once tracing got enabled
+    trace: Option<Vec<MemoryAccess>>,
 
     input: R,
     output: W,
@@ -285,14 +477,17 @@ where
 
 impl<R, W> IntcodeMachine<R, W>
 where
-    R: BufRead,
-    W: Write,
+    R: MachineInput,
+    W: MachineOutput,
 {
-    pub(crate) fn new(tape: Tape, reader: R, writer: W) -> Self {
+    pub fn new(tape: Tape, reader: R, writer: W) -> Self {
         IntcodeMachine {
             tape,
             head_position: 0,
             relative_base: 0,
+            spec: Spec::default(),
+            instructions: standard_instructions(),
+            trace: None,
             input: reader,
             output: writer,
         }
@@ -303,254 +498,204 @@ where
             tape: state.tape,
             head_position: state.head_position,
             relative_base: state.relative_base,
+            spec: state.spec,
+            instructions: state.instructions,
+            trace: None,
             input: reader,
             output: writer,
         }
     }
 
-    pub fn dump_state(&self) -> State {
-        State {
-            tape: self.tape.clone(),
-            relative_base: self.relative_base,
-            head_position: self.head_position,
-        }
+    pub fn spec(&self) -> Spec {
+        self.spec
     }
 
-    fn update_head(&mut self, val: HeadPositionUpdate) -> Result<(), IntcodeMachineError> {
-        // check if new head is within 0..tape.len()
-        if !(0..self.tape.len()).contains(&val) {
-            return Err(IntcodeMachineError::TapeOutOfBoundsError);
-        }
+    pub fn set_spec(&mut self, spec: Spec) {
+        self.spec = spec;
+    }
 
-        self.head_position = val;
-        Ok(())
+    pub fn instruction_set(&self) -> &InstructionSet {
+        &self.instructions
     }
 
-    fn execute_add(
-        &mut self,
-        param_modes: Vec<ParamMode>,
-    ) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        let result =
-            self.tape
-                .mode_read(self.head_position + 1, self.relative_base, param_modes[0])?
-                + self.tape.mode_read(
-                    self.head_position + 2,
-                    self.relative_base,
-                    param_modes[1],
-                )?;
-
-        let output_idx = self.tape.read(self.head_position + 3);
-        self.tape.mode_write(
-            output_idx as usize,
-            self.relative_base,
-            param_modes[2],
-            result,
-        )?;
-
-        Ok(self.head_position + 4)
-    }
-
-    fn execute_mul(
-        &mut self,
-        param_modes: Vec<ParamMode>,
-    ) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        let result =
-            self.tape
-                .mode_read(self.head_position + 1, self.relative_base, param_modes[0])?
-                * self.tape.mode_read(
-                    self.head_position + 2,
-                    self.relative_base,
-                    param_modes[1],
-                )?;
-
-        let output_idx = self.tape.read(self.head_position + 3);
-        self.tape.mode_write(
-            output_idx as usize,
-            self.relative_base,
-            param_modes[2],
-            result,
-        )?;
-
-        Ok(self.head_position + 4)
-    }
-
-    fn execute_less_than(
-        &mut self,
-        param_modes: Vec<ParamMode>,
-    ) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        let param1 =
-            self.tape
-                .mode_read(self.head_position + 1, self.relative_base, param_modes[0])?;
-        let param2 =
-            self.tape
-                .mode_read(self.head_position + 2, self.relative_base, param_modes[1])?;
-        let store_target = self.tape.read(self.head_position + 3);
+    pub fn set_instruction_set(&mut self, instructions: InstructionSet) {
+        self.instructions = Arc::new(instructions);
+    }
 
-        if param1 < param2 {
-            self.tape
-                .mode_write(store_target as usize, self.relative_base, param_modes[2], 1)?;
-        } else {
-            self.tape
-                .mode_write(store_target as usize, self.relative_base, param_modes[2], 0)?;
+    pub fn enable_tracing(&mut self) {
+        if self.trace.is_none() {
+            self.trace = Some(Vec::new());
         }
+    }
 
-        Ok(self.head_position + 4)
+    // all of the memory accesses since the last call
+    pub fn take_trace(&mut self) -> Vec<MemoryAccess> {
+        match self.trace.as_mut() {
+            Some(trace) => std::mem::take(trace),
+            None => Vec::new(),
+        }
     }
 
-    fn execute_jump_true(
-        &mut self,
-        param_modes: Vec<ParamMode>,
-    ) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        let param =
-            self.tape
-                .mode_read(self.head_position + 1, self.relative_base, param_modes[0])?;
-        let jump_target =
-            self.tape
-                .mode_read(self.head_position + 2, self.relative_base, param_modes[1])?;
+    pub fn head_position(&self) -> usize {
+        self.head_position
+    }
 
-        if param != 0 {
-            Ok(jump_target as usize)
-        } else {
-            Ok(self.head_position + 3)
-        }
+    pub fn relative_base(&self) -> isize {
+        self.relative_base
     }
 
-    fn execute_jump_false(
-        &mut self,
-        param_modes: Vec<ParamMode>,
-    ) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        let param =
-            self.tape
-                .mode_read(self.head_position + 1, self.relative_base, param_modes[0])?;
-        let jump_target =
-            self.tape
-                .mode_read(self.head_position + 2, self.relative_base, param_modes[1])?;
+    // value at position 0, which is what the program leaves behind once it halts
+    pub fn result(&mut self) -> isize {
+        self.tape.read(0)
+    }
 
-        if param == 0 {
-            Ok(jump_target as usize)
-        } else {
-            Ok(self.head_position + 3)
-        }
+    pub fn input_mut(&mut self) -> &mut R {
+        &mut self.input
     }
 
-    fn execute_equals(
-        &mut self,
-        param_modes: Vec<ParamMode>,
-    ) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        let param1 =
-            self.tape
-                .mode_read(self.head_position + 1, self.relative_base, param_modes[0])?;
-        let param2 =
-            self.tape
-                .mode_read(self.head_position + 2, self.relative_base, param_modes[1])?;
-        let store_target = self.tape.read(self.head_position + 3);
+    pub fn output_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
 
-        if param1 == param2 {
-            self.tape
-                .mode_write(store_target as usize, self.relative_base, param_modes[2], 1)?;
-        } else {
-            self.tape
-                .mode_write(store_target as usize, self.relative_base, param_modes[2], 0)?;
+    pub fn dump_state(&self) -> State {
+        State {
+            tape: self.tape.clone(),
+            relative_base: self.relative_base,
+            head_position: self.head_position,
+            spec: self.spec,
+            instructions: Arc::clone(&self.instructions),
         }
-
-        Ok(self.head_position + 4)
     }
 
-    fn execute_adjust_relative_base(
-        &mut self,
-        param_modes: Vec<ParamMode>,
-    ) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        let param =
-            self.tape
-                .mode_read(self.head_position + 1, self.relative_base, param_modes[0])?;
+    fn update_head(&mut self, val: usize) -> Result<(), IntcodeMachineError> {
+        // check if new head is within 0..tape.len()
+        if !(0..self.tape.len()).contains(&val) {
+            return Err(IntcodeMachineError::TapeOutOfBoundsError);
+        }
 
-        self.relative_base += param;
-        Ok(self.head_position + 2)
+        self.head_position = val;
+        Ok(())
     }
 
-    fn execute_input(
-        &mut self,
-        param_modes: Vec<ParamMode>,
-    ) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        let output_idx = self.tape.read(self.head_position + 1);
-
-        let mut buffer = String::new();
-        self.input.read_line(&mut buffer).unwrap();
-        let input_value = match buffer.trim().parse::<isize>() {
-            Ok(val) => val,
-            _ => return Err(OpCodeExecutionError::InputFailure),
+    // Unlike day9, earlier specs never touch memory outside of the initial program. Since tape
+    // grows on demand, all of the addresses are checked before the instruction gets executed.
+    fn check_bounds(&self, param_modes: &[ParamMode]) -> Result<(), IntcodeMachineError> {
+        let tape = self.tape.as_slice();
+        let within_tape = |address: Option<isize>| match address {
+            Some(address) => address >= 0 && (address as usize) < tape.len(),
+            None => false,
         };
 
-        self.tape.mode_write(
-            output_idx as usize,
-            self.relative_base,
-            param_modes[0],
-            input_value,
-        )?;
+        for (i, mode) in param_modes.iter().enumerate() {
+            let param_position = self.head_position + 1 + i;
+            if !within_tape(Some(param_position as isize)) {
+                return Err(IntcodeMachineError::TapeOutOfBoundsError);
+            }
 
-        Ok(self.head_position + 2)
-    }
+            let address = match mode {
+                ParamMode::Position => Some(tape[param_position]),
+                // relative base could have been moved arbitrarily far away from the tape
+                ParamMode::Relative => tape[param_position].checked_add(self.relative_base),
+                ParamMode::Immediate => continue,
+            };
+            if !within_tape(address) {
+                return Err(IntcodeMachineError::TapeOutOfBoundsError);
+            }
+        }
 
-    fn execute_output(
-        &mut self,
-        param_modes: Vec<ParamMode>,
-    ) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        let output_val =
-            self.tape
-                .mode_read(self.head_position + 1, self.relative_base, param_modes[0])?;
-        writeln!(&mut self.output, "{}", output_val).unwrap();
+        Ok(())
+    }
 
-        Ok(self.head_position + 2)
+    fn check_spec(&self, code: isize, decoded: &Decoded) -> Result<(), IntcodeMachineError> {
+        if !self.spec.supports(decoded.op_code, &decoded.param_modes) {
+            return Err(IntcodeMachineError::UnsupportedInstruction(code));
+        }
+        if !self.spec.has_unbounded_memory() {
+            self.check_bounds(&decoded.param_modes)?;
+        }
+        Ok(())
     }
 
-    fn execute_op(&mut self, op: OpCode) -> Result<HeadPositionUpdate, OpCodeExecutionError> {
-        use OpCode::*;
-        match op {
-            Add(param_modes) => self.execute_add(param_modes),
-            Mul(param_modes) => self.execute_mul(param_modes),
-            Jt(param_modes) => self.execute_jump_true(param_modes),
-            Jf(param_modes) => self.execute_jump_false(param_modes),
-            Lt(param_modes) => self.execute_less_than(param_modes),
-            Eq(param_modes) => self.execute_equals(param_modes),
-            Rbo(param_modes) => self.execute_adjust_relative_base(param_modes),
-            In(param_modes) => self.execute_input(param_modes),
-            Out(param_modes) => self.execute_output(param_modes.clone()),
+    pub fn step(&mut self) -> Result<StepOutcome, IntcodeMachineError> {
+        let code = self.tape.read(self.head_position);
+        // unknown instructions and invalid parameter modes can't be executed
+        let decoded = match self.instructions.decode(code) {
+            Some(decoded) => decoded,
+            None => return Err(IntcodeMachineError::ExecutionFailure),
+        };
+        self.check_spec(code, &decoded)?;
 
-            Halt => Err(OpCodeExecutionError::ExecutionFinished),
-            Er(_) => Err(OpCodeExecutionError::ExecutionFailure),
+        let mut operands = Operands {
+            tape: &mut self.tape,
+            head_position: self.head_position,
+            relative_base: &mut self.relative_base,
+            param_modes: &decoded.param_modes,
+            input: &mut self.input,
+            output: &mut self.output,
+            trace: self.trace.as_mut(),
+        };
+        let flow = decoded.instruction.execute(&mut operands)?;
+        let next_instruction = self.head_position + 1 + decoded.param_modes.len();
+
+        let instruction = self.head_position;
+        if let (Some(trace), false) = (self.trace.as_mut(), flow == Flow::AwaitInput) {
+            trace.extend((instruction..next_instruction).map(|address| MemoryAccess {
+                kind: AccessKind::Execute,
+                address,
+                instruction,
+            }));
         }
+
+        match flow {
+            Flow::Continue => self.update_head(next_instruction)?,
+            Flow::Jump(target) => self.update_head(target)?,
+            // head is left on the instruction so that it is retried on the next step
+            Flow::AwaitInput => return Ok(StepOutcome::AwaitingInput),
+            Flow::Halt => return Ok(StepOutcome::Halted),
+        }
+        Ok(StepOutcome::Executed)
     }
 
-    pub(crate) fn run(&mut self) -> Result<isize, IntcodeMachineError> {
+    pub fn run(&mut self) -> Result<isize, IntcodeMachineError> {
         loop {
-            let op = OpCode::from(self.tape.read(self.head_position));
-            let head_update = match self.execute_op(op) {
-                Err(err) => match err {
-                    OpCodeExecutionError::ExecutionFinished => {
-                        return Ok(self.tape.read(0));
-                    }
-                    OpCodeExecutionError::InputFailure => {
-                        return Err(IntcodeMachineError::InputFailure(self.dump_state()));
-                    }
-                    _ => {
-                        return Err(IntcodeMachineError::ExecutionFailure);
-                    }
-                },
-                Ok(head_update) => head_update,
-            };
+            match self.step()? {
+                StepOutcome::Executed => (),
+                StepOutcome::AwaitingInput => {
+                    return Err(IntcodeMachineError::InputFailure(self.dump_state()));
+                }
+                StepOutcome::Halted => return Ok(self.result()),
+            }
+        }
+    }
+}
 
-            self.update_head(head_update)?;
+impl<R, W> IntcodeMachine<R, W>
+where
+    R: MachineInput + Clone,
+    W: MachineOutput + Clone,
+{
+    // Independent copy of the machine, including any queued input and output. The memory is
+    // shared until either of the machines writes to it, at which point that one gets its own
+    // copy of the whole tape. Tracing stays enabled in the fork, but it only records accesses
+    // made after the fork.
+    pub fn fork(&self) -> Self {
+        IntcodeMachine {
+            tape: self.tape.clone(),
+            head_position: self.head_position,
+            relative_base: self.relative_base,
+            spec: self.spec,
+            instructions: Arc::clone(&self.instructions),
+            trace: self.trace.as_ref().map(|_| Vec::new()),
+            input: self.input.clone(),
+            output: self.output.clone(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::io;
-
-    use crate::intcode_machine::IntcodeMachineError::InputFailure;
-
     use super::*;
+    use crate::utils;
 
     #[test]
     fn intcode_machine_still_works_for_day2_part1() {
@@ -562,7 +707,7 @@ mod tests {
         let mut dummy_out = Vec::new();
         assert_eq!(
             4_138_687,
-            IntcodeMachine::new(day2_tape, &dummy_in[..], &mut dummy_out)
+            IntcodeMachine::new(day2_tape, LineInput::new(&dummy_in[..]), &mut dummy_out)
                 .run()
                 .unwrap()
         )
@@ -575,7 +720,7 @@ mod tests {
         let input = b"1";
         let mut output = Vec::new();
 
-        IntcodeMachine::new(tape, &input[..], &mut output)
+        IntcodeMachine::new(tape, LineInput::new(&input[..]), &mut output)
             .run()
             .unwrap();
 
@@ -593,7 +738,7 @@ mod tests {
         let input = b"5";
         let mut output = Vec::new();
 
-        IntcodeMachine::new(tape, &input[..], &mut output)
+        IntcodeMachine::new(tape, LineInput::new(&input[..]), &mut output)
             .run()
             .unwrap();
 
@@ -614,7 +759,7 @@ mod tests {
         let dummy_in = b"";
         let mut dummy_out = Vec::new();
 
-        IntcodeMachine::new(tape, &dummy_in[..], &mut dummy_out)
+        IntcodeMachine::new(tape, LineInput::new(&dummy_in[..]), &mut dummy_out)
             .run()
             .unwrap();
 
@@ -629,7 +774,7 @@ mod tests {
         let dummy_in = b"";
         let mut dummy_out = Vec::new();
 
-        IntcodeMachine::new(tape, &dummy_in[..], &mut dummy_out)
+        IntcodeMachine::new(tape, LineInput::new(&dummy_in[..]), &mut dummy_out)
             .run()
             .unwrap();
 
@@ -649,7 +794,7 @@ mod tests {
         let dummy_in = b"";
         let mut dummy_out = Vec::new();
 
-        IntcodeMachine::new(tape, &dummy_in[..], &mut dummy_out)
+        IntcodeMachine::new(tape, LineInput::new(&dummy_in[..]), &mut dummy_out)
             .run()
             .unwrap();
 
@@ -661,4 +806,618 @@ mod tests {
                 .to_owned()
         );
     }
+
+    #[test]
+    fn memory_accesses_are_traced_once_enabled() {
+        let mut machine = IntcodeMachine::new(
+            Tape::new(vec![1, 5, 6, 0, 99, 3, 4]),
+            ValueQueue::new(),
+            ValueQueue::new(),
+        );
+        machine.step().unwrap();
+        assert!(machine.take_trace().is_empty());
+
+        let mut machine = IntcodeMachine::new(
+            Tape::new(vec![1, 5, 6, 0, 99, 3, 4]),
+            ValueQueue::new(),
+            ValueQueue::new(),
+        );
+        machine.enable_tracing();
+        machine.run().unwrap();
+
+        let access = |kind, address, instruction| MemoryAccess {
+            kind,
+            address,
+            instruction,
+        };
+        let write = AccessKind::Write {
+            previous: 1,
+            value: 7,
+        };
+        assert_eq!(
+            vec![
+                access(AccessKind::Read, 5, 0),
+                access(AccessKind::Read, 6, 0),
+                access(write, 0, 0),
+                access(AccessKind::Execute, 0, 0),
+                access(AccessKind::Execute, 1, 0),
+                access(AccessKind::Execute, 2, 0),
+                access(AccessKind::Execute, 3, 0),
+                access(AccessKind::Execute, 4, 4),
+            ],
+            machine.take_trace()
+        );
+        assert!(machine.take_trace().is_empty());
+    }
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct FailingReader;
+
+    impl io::Read for FailingReader {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("device unplugged"))
+        }
+    }
+
+    #[test]
+    fn line_input_accepts_multiple_values_per_line() {
+        let mut input = LineInput::new(&b"1, 2 3\n\n  -4,5,\n"[..]);
+
+        let values: Vec<_> = std::iter::from_fn(|| input.read_value()).collect();
+        assert_eq!(vec![1, 2, 3, -4, 5], values);
+        assert!(matches!(input.take_error(), Some(InputError::EndOfInput)));
+        assert!(input.take_error().is_none());
+    }
+
+    #[test]
+    fn line_input_distinguishes_failures() {
+        let mut input = LineInput::new(&b"7\n1,x,3\n8\n"[..]);
+        assert_eq!(Some(7), input.read_value());
+        assert_eq!(None, input.read_value());
+        match input.take_error() {
+            Some(InputError::InvalidValue { line, token }) => {
+                assert_eq!(2, line);
+                assert_eq!("x", token);
+            }
+            other => panic!("expected invalid value, got {:?}", other),
+        }
+        // the whole malformed line got discarded
+        assert_eq!(Some(8), input.read_value());
+
+        let mut input = LineInput::new(io::BufReader::new(FailingReader));
+        assert_eq!(None, input.read_value());
+        assert!(matches!(input.take_error(), Some(InputError::IoError(_))));
+    }
+
+    #[test]
+    fn input_errors_stop_the_machine_without_losing_its_state() {
+        let echo = || Tape::new(vec![3, 0, 4, 0, 99]);
+
+        let mut machine = IntcodeMachine::new(echo(), LineInput::new(&b""[..]), ValueQueue::new());
+        match machine.run() {
+            Err(IntcodeMachineError::InputError(InputError::EndOfInput)) => (),
+            other => panic!("expected end of input, got {:?}", other),
+        }
+
+        let input = LineInput::new(&b"forty two\n42\n"[..]);
+        let mut machine = IntcodeMachine::new(echo(), input, ValueQueue::new());
+        match machine.run() {
+            Err(IntcodeMachineError::InputError(InputError::InvalidValue { .. })) => (),
+            other => panic!("expected invalid value, got {:?}", other),
+        }
+        assert_eq!(0, machine.head_position());
+        assert_eq!(42, machine.run().unwrap());
+        assert_eq!(vec![42], machine.output_mut().drain());
+    }
+
+    #[test]
+    fn prompt_is_shown_for_every_line_read() {
+        // reads three values and outputs them back
+        let tape = vec![3, 20, 3, 21, 3, 22, 4, 20, 4, 21, 4, 22, 99];
+        let prompts = SharedBuffer::default();
+
+        let input = LineInput::new(&b"1 2\n3\n"[..]).with_prompt("> ", prompts.clone());
+        let mut machine = IntcodeMachine::new(Tape::new(tape), input, ValueQueue::new());
+        machine.run().unwrap();
+
+        assert_eq!(vec![1, 2, 3], machine.output_mut().drain());
+        assert_eq!(b"> > ".to_vec(), *prompts.0.lock().unwrap());
+    }
+
+    #[test]
+    fn forked_machine_continues_independently() {
+        // reads a value, adds it to the relative base and echoes the cell it points to
+        let tape = Tape::new(vec![3, 11, 109, 1, 9, 11, 204, 10, 99, 0, 0, 0, 100, 200]);
+        let mut machine = IntcodeMachine::new(tape, ValueQueue::new(), ValueQueue::from(vec![7]));
+        assert_eq!(StepOutcome::AwaitingInput, machine.step().unwrap());
+
+        let mut fork = machine.fork();
+        machine.input_mut().push(1);
+        fork.input_mut().push(2);
+        machine.run().unwrap();
+        fork.run().unwrap();
+
+        assert_eq!(vec![7, 100], machine.output_mut().drain());
+        assert_eq!(vec![7, 200], fork.output_mut().drain());
+        assert_eq!(2, machine.relative_base());
+        assert_eq!(3, fork.relative_base());
+    }
+
+    #[test]
+    fn fork_shares_memory_until_written() {
+        let machine = IntcodeMachine::new(
+            Tape::new(vec![1101, 1, 1, 5, 99, 0]),
+            ValueQueue::new(),
+            ValueQueue::new(),
+        );
+        let mut fork = machine.fork();
+        assert!(machine
+            .dump_state()
+            .tape()
+            .shares_memory_with(fork.dump_state().tape()));
+
+        fork.step().unwrap();
+        assert!(!machine
+            .dump_state()
+            .tape()
+            .shares_memory_with(fork.dump_state().tape()));
+        assert_eq!(0, machine.dump_state().tape().as_slice()[5]);
+        assert_eq!(2, fork.dump_state().tape().as_slice()[5]);
+        assert_eq!(0, machine.head_position());
+        assert_eq!(4, fork.head_position());
+    }
+
+    mod specs {
+        use super::*;
+
+        fn run_with_spec(tape: Vec<isize>, spec: Spec) -> Result<isize, IntcodeMachineError> {
+            let mut machine =
+                IntcodeMachine::new(Tape::new(tape), ValueQueue::new(), ValueQueue::new());
+            machine.set_spec(spec);
+            machine.run()
+        }
+
+        #[test]
+        fn day2_tape_works_under_every_spec() {
+            let mut day2_tape = utils::read_input_file("day2.input");
+            day2_tape[1] = 12;
+            day2_tape[2] = 2;
+
+            for &spec in [Spec::Day2, Spec::Day5, Spec::Day9].iter() {
+                assert_eq!(4_138_687, run_with_spec(day2_tape.clone(), spec).unwrap());
+            }
+        }
+
+        #[test]
+        fn day5_tape_works_under_day5_spec() {
+            let tape = Tape::new(utils::read_input_file("day5.input"));
+            let mut machine =
+                IntcodeMachine::new(tape, ValueQueue::from(vec![5]), ValueQueue::new());
+            machine.set_spec(Spec::Day5);
+            machine.run().unwrap();
+
+            assert_eq!(vec![584_126], machine.output_mut().drain());
+        }
+
+        #[test]
+        fn instructions_are_restricted_by_spec() {
+            let cases = vec![
+                // relative base offset
+                (vec![109, 1, 99], Spec::Day5),
+                (vec![109, 1, 99], Spec::Day2),
+                // output
+                (vec![4, 0, 99], Spec::Day2),
+                // immediate mode
+                (vec![1101, 1, 1, 0, 99], Spec::Day2),
+                // relative mode
+                (vec![201, 1, 1, 0, 99], Spec::Day5),
+            ];
+
+            for (tape, spec) in cases {
+                let code = tape[0];
+                match run_with_spec(tape, spec) {
+                    Err(IntcodeMachineError::UnsupportedInstruction(c)) => assert_eq!(code, c),
+                    other => panic!("expected unsupported instruction, got {:?}", other),
+                }
+            }
+        }
+
+        #[test]
+        fn only_day9_spec_allows_memory_outside_of_program() {
+            let out_of_range_write = vec![1, 0, 0, 10, 99];
+            let out_of_range_read = vec![1, 10, 0, 0, 99];
+
+            for tape in [out_of_range_write, out_of_range_read].iter() {
+                for &spec in [Spec::Day2, Spec::Day5].iter() {
+                    match run_with_spec(tape.clone(), spec) {
+                        Err(IntcodeMachineError::TapeOutOfBoundsError) => (),
+                        other => panic!("expected out of bounds error, got {:?}", other),
+                    }
+                }
+                assert!(run_with_spec(tape.clone(), Spec::Day9).is_ok());
+            }
+        }
+
+        #[test]
+        fn spec_survives_dumping_and_loading_state() {
+            let mut machine = IntcodeMachine::new(
+                Tape::new(vec![109, 1, 99]),
+                ValueQueue::new(),
+                ValueQueue::new(),
+            );
+            machine.set_spec(Spec::Day5);
+
+            let state = machine.dump_state();
+            assert_eq!(Spec::Day5, state.spec());
+            let mut machine =
+                IntcodeMachine::load_state(state, ValueQueue::new(), ValueQueue::new());
+            assert_eq!(Spec::Day5, machine.spec());
+            match machine.run() {
+                Err(IntcodeMachineError::UnsupportedInstruction(109)) => (),
+                other => panic!("expected unsupported instruction, got {:?}", other),
+            }
+        }
+
+        #[test]
+        fn relative_address_overflow_is_an_error() {
+            // moving the relative base or addressing past isize::MAX
+            for tape in [
+                vec![109, isize::MAX, 109, 1, 99],
+                vec![109, isize::MAX, 204, 1, 99],
+            ] {
+                let mut machine =
+                    IntcodeMachine::new(Tape::new(tape), ValueQueue::new(), ValueQueue::new());
+                machine.enable_tracing();
+                match machine.run() {
+                    Err(IntcodeMachineError::ExecutionFailure) => (),
+                    other => panic!("expected execution failure, got {:?}", other),
+                }
+            }
+        }
+
+        #[test]
+        fn negative_addresses_are_an_error() {
+            // reading and writing at position -1, with and without tracing
+            for tape in [vec![4, -1, 99], vec![1101, 1, 1, -1, 99], vec![3, -1, 99]] {
+                for tracing in [false, true] {
+                    let mut machine = IntcodeMachine::new(
+                        Tape::new(tape.clone()),
+                        ValueQueue::from(vec![1]),
+                        ValueQueue::new(),
+                    );
+                    if tracing {
+                        machine.enable_tracing();
+                    }
+                    match machine.run() {
+                        Err(IntcodeMachineError::ExecutionFailure) => (),
+                        other => panic!("expected execution failure, got {:?}", other),
+                    }
+                }
+            }
+        }
+
+        #[test]
+        fn rejected_instruction_leaves_tape_untouched() {
+            let tape = vec![1, 0, 12, 0, 99];
+            let mut machine = IntcodeMachine::new(
+                Tape::new(tape.clone()),
+                ValueQueue::new(),
+                ValueQueue::new(),
+            );
+            machine.set_spec(Spec::Day2);
+
+            assert!(machine.step().is_err());
+            assert_eq!(&tape[..], machine.dump_state().tape().as_slice());
+        }
+    }
+
+    mod instruction_properties {
+        use super::*;
+        use proptest::prelude::*;
+
+        const TAPE_LEN: usize = 64;
+        // instruction is placed somewhere before the data region, so they never overlap
+        const MAX_HEAD: usize = 16;
+        const READ_SLOTS_START: usize = 24;
+        const WRITE_SLOT_START: usize = 48;
+        const SLOT_SIZE: usize = 10;
+
+        fn read_mode() -> impl Strategy<Value = ParamMode> {
+            prop_oneof![
+                Just(ParamMode::Position),
+                Just(ParamMode::Immediate),
+                Just(ParamMode::Relative),
+            ]
+        }
+
+        fn write_mode() -> impl Strategy<Value = ParamMode> {
+            prop_oneof![Just(ParamMode::Position), Just(ParamMode::Relative)]
+        }
+
+        fn mode_digit(mode: ParamMode) -> isize {
+            match mode {
+                ParamMode::Position => POSITION_MODE as isize,
+                ParamMode::Immediate => IMMEDIATE_MODE as isize,
+                ParamMode::Relative => RELATIVE_MODE as isize,
+            }
+        }
+
+        // operand that makes the parameter resolve to the given address
+        fn address_operand(address: usize, mode: ParamMode, relative_base: isize) -> isize {
+            match mode {
+                ParamMode::Relative => address as isize - relative_base,
+                _ => address as isize,
+            }
+        }
+
+        #[derive(Debug, Clone)]
+        struct Setup {
+            head: usize,
+            relative_base: isize,
+            // offsets within the respective slots of the data region
+            slot_offsets: [usize; 3],
+            filler: isize,
+        }
+
+        fn setup() -> impl Strategy<Value = Setup> {
+            (
+                0..MAX_HEAD,
+                -20isize..20,
+                [0..SLOT_SIZE, 0..SLOT_SIZE, 0..SLOT_SIZE],
+                -5isize..5,
+            )
+                .prop_map(|(head, relative_base, slot_offsets, filler)| Setup {
+                    head,
+                    relative_base,
+                    slot_offsets,
+                    filler,
+                })
+        }
+
+        impl Setup {
+            fn read_address(&self, param: usize) -> usize {
+                READ_SLOTS_START + param * SLOT_SIZE + self.slot_offsets[param]
+            }
+
+            fn write_address(&self) -> usize {
+                WRITE_SLOT_START + self.slot_offsets[2]
+            }
+
+            // places a single instruction on the tape so that its read parameters
+            // resolve to `reads` and its output (if any) goes to `write_address`
+            fn machine(
+                &self,
+                op_code: isize,
+                reads: &[(ParamMode, isize)],
+                write: Option<ParamMode>,
+                input: Vec<isize>,
+            ) -> IntcodeMachine<ValueQueue, ValueQueue> {
+                let mut cells = vec![self.filler; TAPE_LEN];
+                let mut modes: Vec<_> = reads.iter().map(|&(mode, _)| mode).collect();
+                modes.extend(write);
+
+                let encoded_modes = modes
+                    .iter()
+                    .rev()
+                    .fold(0, |acc, &mode| acc * 10 + mode_digit(mode));
+                cells[self.head] = encoded_modes * 100 + op_code;
+
+                for (i, &(mode, value)) in reads.iter().enumerate() {
+                    cells[self.head + 1 + i] = match mode {
+                        ParamMode::Immediate => value,
+                        _ => {
+                            let address = self.read_address(i);
+                            cells[address] = value;
+                            address_operand(address, mode, self.relative_base)
+                        }
+                    };
+                }
+                if let Some(mode) = write {
+                    cells[self.head + 1 + reads.len()] =
+                        address_operand(self.write_address(), mode, self.relative_base);
+                }
+
+                let state = State {
+                    tape: Tape::new(cells),
+                    relative_base: self.relative_base,
+                    head_position: self.head,
+                    spec: Spec::Day9,
+                    instructions: standard_instructions(),
+                };
+                IntcodeMachine::load_state(state, ValueQueue::from(input), ValueQueue::new())
+            }
+        }
+
+        // everything apart from the head and the destination cell must stay the same
+        fn assert_single_write(
+            before: &State,
+            after: &State,
+            address: usize,
+            value: isize,
+        ) -> Result<(), TestCaseError> {
+            let mut expected = before.tape.as_slice().to_vec();
+            expected[address] = value;
+            prop_assert_eq!(&expected[..], after.tape.as_slice());
+            prop_assert_eq!(before.relative_base, after.relative_base);
+            Ok(())
+        }
+
+        fn binary_op_writes(
+            op_code: isize,
+            expected: fn(isize, isize) -> isize,
+            setup: Setup,
+            (mode_a, a): (ParamMode, isize),
+            (mode_b, b): (ParamMode, isize),
+            write: ParamMode,
+        ) -> Result<(), TestCaseError> {
+            let mut machine =
+                setup.machine(op_code, &[(mode_a, a), (mode_b, b)], Some(write), vec![]);
+            let before = machine.dump_state();
+
+            prop_assert_eq!(StepOutcome::Executed, machine.step().unwrap());
+            let after = machine.dump_state();
+            prop_assert_eq!(setup.head + 4, after.head_position);
+            assert_single_write(&before, &after, setup.write_address(), expected(a, b))
+        }
+
+        proptest! {
+            #[test]
+            fn add_writes_sum(
+                setup in setup(),
+                a in (read_mode(), -1000isize..1000),
+                b in (read_mode(), -1000isize..1000),
+                write in write_mode(),
+            ) {
+                binary_op_writes(ADD_OP_CODE, |a, b| a + b, setup, a, b, write)?;
+            }
+
+            #[test]
+            fn mul_writes_product(
+                setup in setup(),
+                a in (read_mode(), -1000isize..1000),
+                b in (read_mode(), -1000isize..1000),
+                write in write_mode(),
+            ) {
+                binary_op_writes(MUL_OP_CODE, |a, b| a * b, setup, a, b, write)?;
+            }
+
+            #[test]
+            fn lt_writes_comparison_result(
+                setup in setup(),
+                a in (read_mode(), -5isize..5),
+                b in (read_mode(), -5isize..5),
+                write in write_mode(),
+            ) {
+                binary_op_writes(LESS_THAN_OP_CODE, |a, b| (a < b) as isize, setup, a, b, write)?;
+            }
+
+            #[test]
+            fn eq_writes_comparison_result(
+                setup in setup(),
+                a in (read_mode(), -5isize..5),
+                b in (read_mode(), -5isize..5),
+                write in write_mode(),
+            ) {
+                binary_op_writes(EQUALS_OP_CODE, |a, b| (a == b) as isize, setup, a, b, write)?;
+            }
+
+            #[test]
+            fn jt_moves_head_only_when_non_zero(
+                setup in setup(),
+                condition in (read_mode(), -3isize..3),
+                target_mode in read_mode(),
+                target in 0..TAPE_LEN as isize,
+            ) {
+                let reads = [condition, (target_mode, target)];
+                let mut machine = setup.machine(JMP_TRUE_OP_CODE, &reads, None, vec![]);
+                let before = machine.dump_state();
+
+                prop_assert_eq!(StepOutcome::Executed, machine.step().unwrap());
+                let after = machine.dump_state();
+                let expected_head = if condition.1 != 0 { target as usize } else { setup.head + 3 };
+                prop_assert_eq!(expected_head, after.head_position);
+                prop_assert_eq!(before.tape.as_slice(), after.tape.as_slice());
+            }
+
+            #[test]
+            fn jf_moves_head_only_when_zero(
+                setup in setup(),
+                condition in (read_mode(), -3isize..3),
+                target_mode in read_mode(),
+                target in 0..TAPE_LEN as isize,
+            ) {
+                let reads = [condition, (target_mode, target)];
+                let mut machine = setup.machine(JMP_FALSE_OP_CODE, &reads, None, vec![]);
+                let before = machine.dump_state();
+
+                prop_assert_eq!(StepOutcome::Executed, machine.step().unwrap());
+                let after = machine.dump_state();
+                let expected_head = if condition.1 == 0 { target as usize } else { setup.head + 3 };
+                prop_assert_eq!(expected_head, after.head_position);
+                prop_assert_eq!(before.tape.as_slice(), after.tape.as_slice());
+            }
+
+            #[test]
+            fn rbo_adjusts_relative_base(
+                setup in setup(),
+                offset in (read_mode(), -1000isize..1000),
+            ) {
+                let mut machine = setup.machine(RLT_BASE_OFFSET_OP_CODE, &[offset], None, vec![]);
+                let before = machine.dump_state();
+
+                prop_assert_eq!(StepOutcome::Executed, machine.step().unwrap());
+                let after = machine.dump_state();
+                prop_assert_eq!(setup.relative_base + offset.1, after.relative_base);
+                prop_assert_eq!(setup.head + 2, after.head_position);
+                prop_assert_eq!(before.tape.as_slice(), after.tape.as_slice());
+            }
+
+            #[test]
+            fn in_writes_next_input_value(
+                setup in setup(),
+                write in write_mode(),
+                value in any::<isize>(),
+            ) {
+                let mut machine = setup.machine(INPUT_OP_CODE, &[], Some(write), vec![value]);
+                let before = machine.dump_state();
+
+                prop_assert_eq!(StepOutcome::Executed, machine.step().unwrap());
+                let after = machine.dump_state();
+                prop_assert_eq!(setup.head + 2, after.head_position);
+                assert_single_write(&before, &after, setup.write_address(), value)?;
+
+                // with no more input available head stays in place
+                machine = IntcodeMachine::load_state(before, ValueQueue::new(), ValueQueue::new());
+                prop_assert_eq!(StepOutcome::AwaitingInput, machine.step().unwrap());
+                prop_assert_eq!(setup.head, machine.dump_state().head_position);
+            }
+
+            #[test]
+            fn out_emits_parameter_value(
+                setup in setup(),
+                value in (read_mode(), any::<isize>()),
+            ) {
+                let mut machine = setup.machine(OUTPUT_OP_CODE, &[value], None, vec![]);
+                let before = machine.dump_state();
+
+                prop_assert_eq!(StepOutcome::Executed, machine.step().unwrap());
+                prop_assert_eq!(vec![value.1], machine.output_mut().drain());
+                let after = machine.dump_state();
+                prop_assert_eq!(setup.head + 2, after.head_position);
+                prop_assert_eq!(before.tape.as_slice(), after.tape.as_slice());
+            }
+
+            #[test]
+            fn writes_in_immediate_mode_are_rejected(
+                setup in setup(),
+                op_code in prop_oneof![
+                    Just(ADD_OP_CODE),
+                    Just(MUL_OP_CODE),
+                    Just(LESS_THAN_OP_CODE),
+                    Just(EQUALS_OP_CODE),
+                ],
+                a in (read_mode(), -1000isize..1000),
+                b in (read_mode(), -1000isize..1000),
+            ) {
+                let write = Some(ParamMode::Immediate);
+                let mut machine = setup.machine(op_code, &[a, b], write, vec![]);
+                match machine.step() {
+                    Err(IntcodeMachineError::ExecutionFailure) => (),
+                    other => prop_assert!(false, "expected execution failure, got {:?}", other),
+                }
+            }
+        }
+    }
 }
@@ -0,0 +1,21 @@
+pub mod arcade;
+pub mod ascii;
+pub mod async_machine;
+pub mod corpus;
+pub mod droid;
+pub mod heatmap;
+pub mod inference;
+pub mod input_search;
+pub mod instructions;
+pub mod intcode_machine;
+pub mod memory_view;
+pub mod network;
+pub mod robot;
+pub mod scaffold;
+pub mod self_modification;
+pub mod session;
+pub mod tractor_beam;
+pub mod utils;
+pub mod wasm;
+#[cfg(test)]
+mod wat_interpreter;
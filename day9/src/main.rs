@@ -1,13 +1,11 @@
-use crate::intcode_machine::{IntcodeMachine, Tape};
-
-mod intcode_machine;
-mod utils;
+use day9::intcode_machine::{IntcodeMachine, LineInput, Tape};
+use day9::utils;
 
 fn do_part1(tape: Tape) {
     let fake_input = b"1";
     let mut output = Vec::new();
 
-    IntcodeMachine::new(tape, &fake_input[..], &mut output)
+    IntcodeMachine::new(tape, LineInput::new(&fake_input[..]), &mut output)
         .run()
         .unwrap();
 
@@ -23,7 +21,7 @@ fn do_part2(tape: Tape) {
     let fake_input = b"2";
     let mut output = Vec::new();
 
-    IntcodeMachine::new(tape, &fake_input[..], &mut output)
+    IntcodeMachine::new(tape, LineInput::new(&fake_input[..]), &mut output)
         .run()
         .unwrap();
 
@@ -36,7 +34,7 @@ fn do_part2(tape: Tape) {
 }
 
 fn main() {
-    let tape = Tape::new(utils::read_input_file("day9.input"));
+    let tape = Tape::new(intcode_loader::load_program_from_args("day9.input"));
 
     do_part1(tape.clone());
     do_part2(tape);
@@ -0,0 +1,294 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use crate::intcode_machine::State;
+
+const DEFAULT_ROW_WIDTH: usize = 10;
+const ADDRESS_WIDTH: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Radix {
+    Decimal,
+    Hexadecimal,
+}
+
+impl Radix {
+    fn format(self, value: isize) -> String {
+        match self {
+            Radix::Decimal => value.to_string(),
+            Radix::Hexadecimal if value < 0 => format!("-{:#x}", value.unsigned_abs()),
+            Radix::Hexadecimal => format!("{:#x}", value),
+        }
+    }
+
+    fn format_address(self, address: usize) -> String {
+        match self {
+            Radix::Decimal => format!("{:>width$}", address, width = ADDRESS_WIDTH),
+            Radix::Hexadecimal => format!("{:>#width$x}", address, width = ADDRESS_WIDTH),
+        }
+    }
+}
+
+// Memory laid out in rows prefixed with the address of their first cell.
+// Cell under the head is shown as [x], cell pointed to by the relative base as <x>
+// and cells that differ from the compared snapshot are followed by '*'.
+pub struct MemoryView<'a> {
+    state: &'a State,
+    row_width: usize,
+    radix: Radix,
+    changed: HashSet<usize>,
+}
+
+impl<'a> MemoryView<'a> {
+    pub fn new(state: &'a State) -> Self {
+        MemoryView {
+            state,
+            row_width: DEFAULT_ROW_WIDTH,
+            radix: Radix::Decimal,
+            changed: HashSet::new(),
+        }
+    }
+
+    pub fn with_row_width(mut self, row_width: usize) -> Self {
+        self.row_width = row_width.max(1);
+        self
+    }
+
+    pub fn with_radix(mut self, radix: Radix) -> Self {
+        self.radix = radix;
+        self
+    }
+
+    pub fn compared_to(mut self, before: &State) -> Self {
+        self.changed = MemoryDiff::new(before, self.state)
+            .changes()
+            .iter()
+            .map(|change| change.address)
+            .collect();
+        self
+    }
+
+    fn cell(&self, address: usize, value: isize) -> String {
+        let mut cell = self.radix.format(value);
+        if self.state.relative_base() >= 0 && address == self.state.relative_base() as usize {
+            cell = format!("<{}>", cell);
+        }
+        if address == self.state.head_position() {
+            cell = format!("[{}]", cell);
+        }
+        if self.changed.contains(&address) {
+            cell.push('*');
+        }
+        cell
+    }
+}
+
+impl<'a> fmt::Display for MemoryView<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "head: {}, relative base: {}",
+            self.state.head_position(),
+            self.state.relative_base()
+        )?;
+
+        let cells: Vec<_> = self
+            .state
+            .tape()
+            .as_slice()
+            .iter()
+            .enumerate()
+            .map(|(address, &value)| self.cell(address, value))
+            .collect();
+        let cell_width = cells.iter().map(|cell| cell.len()).max().unwrap_or(0);
+
+        for (row, row_cells) in cells.chunks(self.row_width).enumerate() {
+            write!(f, "{}:", self.radix.format_address(row * self.row_width))?;
+            for cell in row_cells {
+                write!(f, " {:>width$}", cell, width = cell_width)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellChange {
+    pub address: usize,
+    pub before: isize,
+    pub after: isize,
+}
+
+// Tapes grow on demand, so cells missing from either of the snapshots are treated as 0.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MemoryDiff {
+    head_position: (usize, usize),
+    relative_base: (isize, isize),
+    changes: Vec<CellChange>,
+}
+
+impl MemoryDiff {
+    pub fn new(before: &State, after: &State) -> Self {
+        let before_tape = before.tape().as_slice();
+        let after_tape = after.tape().as_slice();
+        let cell = |tape: &[isize], address: usize| tape.get(address).cloned().unwrap_or(0);
+
+        let changes = (0..before_tape.len().max(after_tape.len()))
+            .map(|address| CellChange {
+                address,
+                before: cell(before_tape, address),
+                after: cell(after_tape, address),
+            })
+            .filter(|change| change.before != change.after)
+            .collect();
+
+        MemoryDiff {
+            head_position: (before.head_position(), after.head_position()),
+            relative_base: (before.relative_base(), after.relative_base()),
+            changes,
+        }
+    }
+
+    pub fn changes(&self) -> &[CellChange] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+            && self.head_position.0 == self.head_position.1
+            && self.relative_base.0 == self.relative_base.1
+    }
+}
+
+impl fmt::Display for MemoryDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+
+        if self.head_position.0 != self.head_position.1 {
+            writeln!(
+                f,
+                "head: {} -> {}",
+                self.head_position.0, self.head_position.1
+            )?;
+        }
+        if self.relative_base.0 != self.relative_base.1 {
+            writeln!(
+                f,
+                "relative base: {} -> {}",
+                self.relative_base.0, self.relative_base.1
+            )?;
+        }
+        for change in self.changes.iter() {
+            writeln!(
+                f,
+                "{:>width$}: {} -> {}",
+                change.address,
+                change.before,
+                change.after,
+                width = ADDRESS_WIDTH
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode_machine::{IntcodeMachine, Tape, ValueQueue};
+
+    fn state_after_steps(tape: Vec<isize>, steps: usize) -> State {
+        let mut machine =
+            IntcodeMachine::new(Tape::new(tape), ValueQueue::new(), ValueQueue::new());
+        for _ in 0..steps {
+            machine.step().unwrap();
+        }
+        machine.dump_state()
+    }
+
+    #[test]
+    fn head_and_relative_base_are_highlighted() {
+        let state = state_after_steps(vec![109, 3, 1101, 7, -8, 9, 99], 1);
+
+        assert_eq!(
+            "head: 2, relative base: 3\n     \
+             0:    109      3 [1101]    <7>\n     \
+             4:     -8      9     99\n",
+            MemoryView::new(&state).with_row_width(4).to_string()
+        );
+    }
+
+    #[test]
+    fn memory_can_be_shown_in_hexadecimal() {
+        let state = State::new_from_tape(Tape::new(vec![255, -16, 1, 2, 3]));
+
+        assert_eq!(
+            "head: 0, relative base: 0\n   \
+             0x0: [<0xff>]    -0x10      0x1\n   \
+             0x3:      0x2      0x3\n",
+            MemoryView::new(&state)
+                .with_row_width(3)
+                .with_radix(Radix::Hexadecimal)
+                .to_string()
+        );
+    }
+
+    #[test]
+    fn diff_lists_changed_cells_and_registers() {
+        let tape = vec![1, 0, 0, 0, 109, 4, 1, 0, 0, 10, 99];
+        let before = state_after_steps(tape.clone(), 0);
+        let after = state_after_steps(tape, 3);
+
+        let diff = MemoryDiff::new(&before, &after);
+        assert_eq!(
+            vec![
+                CellChange {
+                    address: 0,
+                    before: 1,
+                    after: 2
+                },
+                CellChange {
+                    address: 10,
+                    before: 99,
+                    after: 4
+                },
+            ],
+            diff.changes()
+        );
+        assert_eq!(
+            "head: 0 -> 10\nrelative base: 0 -> 4\n     0: 1 -> 2\n    10: 99 -> 4\n",
+            diff.to_string()
+        );
+    }
+
+    #[test]
+    fn cells_beyond_shorter_snapshot_are_treated_as_zero() {
+        let before = State::new_from_tape(Tape::new(vec![1, 2]));
+        let after = State::new_from_tape(Tape::new(vec![1, 2, 0, 5]));
+
+        let diff = MemoryDiff::new(&before, &after);
+        assert_eq!(1, diff.changes().len());
+        assert_eq!(3, diff.changes()[0].address);
+        assert!(MemoryDiff::new(&before, &before).is_empty());
+        assert_eq!(
+            "no changes\n",
+            MemoryDiff::new(&before, &before).to_string()
+        );
+    }
+
+    #[test]
+    fn changed_cells_are_marked_in_the_view() {
+        let before = State::new_from_tape(Tape::new(vec![1, 2, 3]));
+        let after = State::new_from_tape(Tape::new(vec![1, 7, 3]));
+
+        assert_eq!(
+            "head: 0, relative base: 0\n     0: [<1>]    7*     3\n",
+            MemoryView::new(&after).compared_to(&before).to_string()
+        );
+    }
+}
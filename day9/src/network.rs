@@ -0,0 +1,425 @@
+use std::collections::VecDeque;
+
+use crate::intcode_machine::{IntcodeMachine, IntcodeMachineError, StepOutcome, Tape, ValueQueue};
+
+pub const DEFAULT_NAT_ADDRESS: usize = 255;
+
+// upper bound on instructions a single machine can execute before yielding to the next one,
+// so that a machine that never touches its input cannot starve the rest of the network
+const STEPS_PER_TURN: usize = 10_000;
+
+const EMPTY_INPUT: isize = -1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Packet {
+    pub address: usize,
+    pub x: isize,
+    pub y: isize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NetworkEvent {
+    NatReceived(Packet),
+    NatDelivered(Packet),
+}
+
+#[derive(Debug)]
+pub enum NetworkError {
+    MachineFailure(usize, IntcodeMachineError),
+    InvalidAddress(isize),
+    UnknownAddress(Packet),
+    Deadlock,
+    AllMachinesHalted,
+}
+
+struct Nic {
+    address: usize,
+    machine: IntcodeMachine<ValueQueue, ValueQueue>,
+    queue: VecDeque<Packet>,
+    pending_output: Vec<isize>,
+    idle: bool,
+    halted: bool,
+}
+
+impl Nic {
+    fn boot(tape: Tape, address: usize) -> Self {
+        let input = ValueQueue::from(vec![address as isize]);
+        let machine = IntcodeMachine::new(tape, input, ValueQueue::new());
+
+        Nic {
+            address,
+            machine,
+            queue: VecDeque::new(),
+            pending_output: Vec::new(),
+            idle: false,
+            halted: false,
+        }
+    }
+
+    fn collect_output(&mut self, sent: &mut Vec<Packet>) -> Result<(), NetworkError> {
+        self.pending_output
+            .extend(self.machine.output_mut().drain());
+
+        while self.pending_output.len() >= 3 {
+            let triple: Vec<_> = self.pending_output.drain(..3).collect();
+            if triple[0] < 0 {
+                return Err(NetworkError::InvalidAddress(triple[0]));
+            }
+
+            sent.push(Packet {
+                address: triple[0] as usize,
+                x: triple[1],
+                y: triple[2],
+            });
+        }
+
+        Ok(())
+    }
+
+    // runs the machine until it either halts, exhausts its step budget or asks for input
+    // for the second time while having nothing queued
+    fn take_turn(&mut self) -> Result<Vec<Packet>, NetworkError> {
+        let mut sent = Vec::new();
+        let mut received = false;
+        let mut polled_empty = false;
+
+        for _ in 0..STEPS_PER_TURN {
+            let outcome = self.machine.step();
+            match outcome.map_err(|err| NetworkError::MachineFailure(self.address, err))? {
+                StepOutcome::Executed => self.collect_output(&mut sent)?,
+                StepOutcome::AwaitingInput => match self.queue.pop_front() {
+                    Some(packet) => {
+                        self.machine.input_mut().push(packet.x);
+                        self.machine.input_mut().push(packet.y);
+                        received = true;
+                    }
+                    None if polled_empty => break,
+                    None => {
+                        self.machine.input_mut().push(EMPTY_INPUT);
+                        polled_empty = true;
+                    }
+                },
+                StepOutcome::Halted => {
+                    self.halted = true;
+                    break;
+                }
+            }
+        }
+
+        self.idle = polled_empty && !received && sent.is_empty();
+        Ok(sent)
+    }
+}
+
+struct Nat {
+    address: usize,
+    last_packet: Option<Packet>,
+}
+
+pub struct Network {
+    nics: Vec<Nic>,
+    nat: Option<Nat>,
+}
+
+impl Network {
+    pub fn new(tape: Tape, size: usize) -> Self {
+        Network {
+            nics: (0..size)
+                .map(|address| Nic::boot(tape.clone(), address))
+                .collect(),
+            nat: None,
+        }
+    }
+
+    pub fn set_nat(&mut self, address: usize) {
+        assert!(
+            address >= self.nics.len(),
+            "NAT address must not collide with any machine"
+        );
+        self.nat = Some(Nat {
+            address,
+            last_packet: None,
+        });
+    }
+
+    pub fn size(&self) -> usize {
+        self.nics.len()
+    }
+
+    // packets left queued for halted machines are never going to be read
+    pub fn is_idle(&self) -> bool {
+        self.nics
+            .iter()
+            .all(|nic| nic.halted || (nic.idle && nic.queue.is_empty()))
+    }
+
+    // packets sent to machines that have already halted are dropped
+    pub fn send(&mut self, packet: Packet) -> Result<Option<NetworkEvent>, NetworkError> {
+        if packet.address < self.nics.len() {
+            let nic = &mut self.nics[packet.address];
+            if nic.halted {
+                return Ok(None);
+            }
+            nic.queue.push_back(packet);
+            nic.idle = false;
+            return Ok(None);
+        }
+
+        match self.nat.as_mut() {
+            Some(nat) if nat.address == packet.address => {
+                nat.last_packet = Some(packet);
+                Ok(Some(NetworkEvent::NatReceived(packet)))
+            }
+            _ => Err(NetworkError::UnknownAddress(packet)),
+        }
+    }
+
+    // single round-robin pass over all machines. Packets are routed as soon as they are sent,
+    // so machines later in the pass can already react to them.
+    pub fn tick(&mut self) -> Result<Vec<NetworkEvent>, NetworkError> {
+        let mut events = Vec::new();
+
+        for address in 0..self.nics.len() {
+            if self.nics[address].halted {
+                continue;
+            }
+
+            let sent = self.nics[address].take_turn()?;
+            for packet in sent {
+                if let Some(event) = self.send(packet)? {
+                    events.push(event);
+                }
+            }
+        }
+
+        if self.nics.iter().all(|nic| nic.halted) {
+            return Err(NetworkError::AllMachinesHalted);
+        }
+
+        if self.is_idle() {
+            let last_packet = match self.nat.as_ref() {
+                Some(nat) => nat.last_packet,
+                None => None,
+            };
+
+            match last_packet {
+                // nothing could ever wake the network up again
+                Some(_) if self.nics[0].halted => return Err(NetworkError::Deadlock),
+                Some(packet) => {
+                    let wakeup = Packet {
+                        address: 0,
+                        ..packet
+                    };
+                    self.send(wakeup)?;
+                    events.push(NetworkEvent::NatDelivered(wakeup));
+                }
+                None => return Err(NetworkError::Deadlock),
+            }
+        }
+
+        Ok(events)
+    }
+
+    pub fn run_until_nat_packet(&mut self) -> Result<Packet, NetworkError> {
+        loop {
+            for event in self.tick()? {
+                if let NetworkEvent::NatReceived(packet) = event {
+                    return Ok(packet);
+                }
+            }
+        }
+    }
+
+    pub fn run_until_repeated_nat_delivery(&mut self) -> Result<Packet, NetworkError> {
+        let mut last_delivered_y = None;
+        loop {
+            for event in self.tick()? {
+                if let NetworkEvent::NatDelivered(packet) = event {
+                    if last_delivered_y == Some(packet.y) {
+                        return Ok(packet);
+                    }
+                    last_delivered_y = Some(packet.y);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // reads own address, then repeatedly waits for a packet and forwards it to address + 1
+    // with y increased by the value stored at `FORWARDER_INCREMENT_IDX`
+    const FORWARDER_INCREMENT_IDX: usize = 23;
+
+    fn forwarder_tape(increment: isize) -> Tape {
+        let mut tape = vec![
+            3, 100, 3, 101, 1008, 101, -1, 103, 1005, 103, 2, 3, 102, 1001, 100, 1, 104, 4, 104, 4,
+            101, 1001, 102, 1, 102, 4, 102, 1105, 1, 2,
+        ];
+        tape[FORWARDER_INCREMENT_IDX] = increment;
+        Tape::new(tape)
+    }
+
+    #[test]
+    fn packet_is_forwarded_through_every_machine_to_nat() {
+        let mut network = Network::new(forwarder_tape(1), 3);
+        network.set_nat(3);
+        network
+            .send(Packet {
+                address: 0,
+                x: 7,
+                y: 10,
+            })
+            .unwrap();
+
+        assert_eq!(
+            Packet {
+                address: 3,
+                x: 7,
+                y: 13
+            },
+            network.run_until_nat_packet().unwrap()
+        );
+    }
+
+    // same as the forwarder with no increment, apart from the machine at `halted_address`
+    // which halts right after booting
+    fn halting_forwarder_tape(halted_address: isize) -> Tape {
+        Tape::new(vec![
+            3,
+            100,
+            1008,
+            100,
+            halted_address,
+            103,
+            1005,
+            103,
+            33,
+            3,
+            101,
+            1008,
+            101,
+            -1,
+            103,
+            1005,
+            103,
+            9,
+            3,
+            102,
+            1001,
+            100,
+            1,
+            104,
+            4,
+            104,
+            4,
+            101,
+            4,
+            102,
+            1105,
+            1,
+            9,
+            99,
+        ])
+    }
+
+    #[test]
+    fn nat_wakes_up_idle_network_until_it_repeats_itself() {
+        let mut network = Network::new(forwarder_tape(0), 4);
+        // last machine forwards to the address right after its own
+        network.set_nat(network.size());
+        network
+            .send(Packet {
+                address: 0,
+                x: 3,
+                y: 42,
+            })
+            .unwrap();
+
+        assert_eq!(
+            Packet {
+                address: 0,
+                x: 3,
+                y: 42
+            },
+            network.run_until_repeated_nat_delivery().unwrap()
+        );
+    }
+
+    #[test]
+    fn idle_network_without_nat_packet_is_a_deadlock() {
+        let mut network = Network::new(forwarder_tape(1), 2);
+        network.set_nat(2);
+
+        match network.tick() {
+            Err(NetworkError::Deadlock) => (),
+            other => panic!("expected deadlock, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn traffic_to_halted_machine_does_not_keep_network_busy() {
+        let mut network = Network::new(halting_forwarder_tape(1), 3);
+        network.set_nat(3);
+        network
+            .send(Packet {
+                address: 0,
+                x: 1,
+                y: 2,
+            })
+            .unwrap();
+
+        // the packet got stuck at the halted machine, so it never reaches the NAT
+        match network.run_until_nat_packet() {
+            Err(NetworkError::Deadlock) => (),
+            other => panic!("expected deadlock, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn nat_cannot_wake_up_halted_first_machine() {
+        let mut network = Network::new(halting_forwarder_tape(0), 3);
+        network.set_nat(3);
+        network
+            .send(Packet {
+                address: 1,
+                x: 1,
+                y: 2,
+            })
+            .unwrap();
+
+        match network.run_until_repeated_nat_delivery() {
+            Err(NetworkError::Deadlock) => (),
+            other => panic!("expected deadlock, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn packets_to_unknown_addresses_are_rejected() {
+        let mut network = Network::new(forwarder_tape(1), 2);
+        network
+            .send(Packet {
+                address: 1,
+                x: 0,
+                y: 0,
+            })
+            .unwrap();
+
+        match network.tick() {
+            Err(NetworkError::UnknownAddress(packet)) => assert_eq!(2, packet.address),
+            other => panic!("expected unknown address, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn network_reports_when_all_machines_halted() {
+        let mut network = Network::new(Tape::new(vec![3, 0, 99]), 2);
+
+        match network.tick() {
+            Err(NetworkError::AllMachinesHalted) => (),
+            other => panic!("expected all machines halted, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,261 @@
+use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
+
+use crate::intcode_machine::{IntcodeMachine, IntcodeMachineError, StepOutcome, Tape, ValueQueue};
+
+const BLACK: isize = 0;
+const WHITE: isize = 1;
+
+const TURN_LEFT: isize = 0;
+const TURN_RIGHT: isize = 1;
+
+pub type Position = (isize, isize);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Colour {
+    Black,
+    White,
+}
+
+impl TryFrom<isize> for Colour {
+    type Error = RobotError;
+
+    fn try_from(value: isize) -> Result<Self, Self::Error> {
+        match value {
+            BLACK => Ok(Colour::Black),
+            WHITE => Ok(Colour::White),
+            _ => Err(RobotError::InvalidColour(value)),
+        }
+    }
+}
+
+impl From<Colour> for isize {
+    fn from(colour: Colour) -> Self {
+        match colour {
+            Colour::Black => BLACK,
+            Colour::White => WHITE,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+impl Direction {
+    fn turn_left(self) -> Self {
+        use Direction::*;
+        match self {
+            Up => Left,
+            Left => Down,
+            Down => Right,
+            Right => Up,
+        }
+    }
+
+    fn turn_right(self) -> Self {
+        use Direction::*;
+        match self {
+            Up => Right,
+            Right => Down,
+            Down => Left,
+            Left => Up,
+        }
+    }
+
+    // y grows downwards so that the grid can be rendered row by row
+    fn step(self, (x, y): Position) -> Position {
+        use Direction::*;
+        match self {
+            Up => (x, y - 1),
+            Right => (x + 1, y),
+            Down => (x, y + 1),
+            Left => (x - 1, y),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum RobotError {
+    MachineFailure(IntcodeMachineError),
+    InvalidColour(isize),
+    InvalidTurn(isize),
+}
+
+impl From<IntcodeMachineError> for RobotError {
+    fn from(err: IntcodeMachineError) -> Self {
+        RobotError::MachineFailure(err)
+    }
+}
+
+pub struct PaintingRobot {
+    machine: IntcodeMachine<ValueQueue, ValueQueue>,
+    panels: HashMap<Position, Colour>,
+    painted: HashSet<Position>,
+    position: Position,
+    direction: Direction,
+}
+
+impl PaintingRobot {
+    pub fn new(tape: Tape, starting_colour: Colour) -> Self {
+        let mut panels = HashMap::new();
+        panels.insert((0, 0), starting_colour);
+
+        PaintingRobot {
+            machine: IntcodeMachine::new(tape, ValueQueue::new(), ValueQueue::new()),
+            panels,
+            painted: HashSet::new(),
+            position: (0, 0),
+            direction: Direction::Up,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn panel_colour(&self, position: Position) -> Colour {
+        *self.panels.get(&position).unwrap_or(&Colour::Black)
+    }
+
+    pub fn painted_panels(&self) -> usize {
+        self.painted.len()
+    }
+
+    fn apply_instruction(&mut self, paint: isize, turn: isize) -> Result<(), RobotError> {
+        let colour = Colour::try_from(paint)?;
+        self.panels.insert(self.position, colour);
+        self.painted.insert(self.position);
+
+        self.direction = match turn {
+            TURN_LEFT => self.direction.turn_left(),
+            TURN_RIGHT => self.direction.turn_right(),
+            _ => return Err(RobotError::InvalidTurn(turn)),
+        };
+        self.position = self.direction.step(self.position);
+
+        Ok(())
+    }
+
+    pub fn run(&mut self) -> Result<(), RobotError> {
+        loop {
+            match self.machine.step()? {
+                StepOutcome::Executed => {
+                    if self.machine.output_mut().len() == 2 {
+                        let paint = self.machine.output_mut().pop().unwrap();
+                        let turn = self.machine.output_mut().pop().unwrap();
+                        self.apply_instruction(paint, turn)?;
+                    }
+                }
+                StepOutcome::AwaitingInput => {
+                    let camera_reading = self.panel_colour(self.position).into();
+                    self.machine.input_mut().push(camera_reading);
+                }
+                StepOutcome::Halted => return Ok(()),
+            }
+        }
+    }
+
+    // draws the smallest area containing all of the white panels
+    pub fn render(&self) -> String {
+        let white_panels: Vec<_> = self
+            .panels
+            .iter()
+            .filter(|(_, &colour)| colour == Colour::White)
+            .map(|(&position, _)| position)
+            .collect();
+
+        if white_panels.is_empty() {
+            return String::new();
+        }
+
+        let min_x = white_panels.iter().map(|&(x, _)| x).min().unwrap();
+        let max_x = white_panels.iter().map(|&(x, _)| x).max().unwrap();
+        let min_y = white_panels.iter().map(|&(_, y)| y).min().unwrap();
+        let max_y = white_panels.iter().map(|&(_, y)| y).max().unwrap();
+
+        let mut rendered = String::new();
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                rendered.push(match self.panel_colour((x, y)) {
+                    Colour::White => '#',
+                    Colour::Black => '.',
+                });
+            }
+            rendered.push('\n');
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // ignores its input and replays the (paint, turn) pairs from the puzzle example
+    fn example_tape() -> Tape {
+        Tape::new(vec![
+            109, 18, 3, 33, 204, 0, 204, 1, 109, 2, 1001, 32, -1, 32, 1005, 32, 2, 99, 1, 0, 0, 0,
+            1, 0, 1, 0, 0, 1, 1, 0, 1, 0, 7,
+        ])
+    }
+
+    // paints the panel with the colour it sees, turns left and halts
+    fn copying_tape() -> Tape {
+        Tape::new(vec![3, 9, 4, 9, 104, 0, 99])
+    }
+
+    #[test]
+    fn example_paints_six_panels() {
+        let mut robot = PaintingRobot::new(example_tape(), Colour::Black);
+        robot.run().unwrap();
+
+        assert_eq!(6, robot.painted_panels());
+        assert_eq!((0, -1), robot.position());
+        assert_eq!(Direction::Left, robot.direction());
+    }
+
+    #[test]
+    fn example_renders_as_expected() {
+        let mut robot = PaintingRobot::new(example_tape(), Colour::Black);
+        robot.run().unwrap();
+
+        assert_eq!("..#\n..#\n##.\n", robot.render());
+    }
+
+    #[test]
+    fn starting_colour_is_fed_to_the_machine() {
+        let mut robot = PaintingRobot::new(copying_tape(), Colour::White);
+        robot.run().unwrap();
+        assert_eq!(Colour::White, robot.panel_colour((0, 0)));
+        assert_eq!(1, robot.painted_panels());
+
+        let mut robot = PaintingRobot::new(copying_tape(), Colour::Black);
+        robot.run().unwrap();
+        assert_eq!(Colour::Black, robot.panel_colour((0, 0)));
+        assert_eq!("", robot.render());
+    }
+
+    #[test]
+    fn invalid_instructions_are_rejected() {
+        let mut robot = PaintingRobot::new(Tape::new(vec![104, 2, 104, 0, 99]), Colour::Black);
+        match robot.run() {
+            Err(RobotError::InvalidColour(2)) => (),
+            other => panic!("expected invalid colour, got {:?}", other),
+        }
+
+        let mut robot = PaintingRobot::new(Tape::new(vec![104, 1, 104, 5, 99]), Colour::Black);
+        match robot.run() {
+            Err(RobotError::InvalidTurn(5)) => (),
+            other => panic!("expected invalid turn, got {:?}", other),
+        }
+    }
+}
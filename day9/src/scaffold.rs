@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+
+use crate::ascii::AsciiMachine;
+use crate::intcode_machine::{IntcodeMachineError, StepOutcome, Tape};
+
+pub type Position = (isize, isize);
+
+#[derive(Debug)]
+pub enum ScaffoldError {
+    MachineFailure(IntcodeMachineError),
+    // camera only ever produces the image and halts
+    UnexpectedInputRequest,
+    InvalidSymbol(char),
+}
+
+impl From<IntcodeMachineError> for ScaffoldError {
+    fn from(err: IntcodeMachineError) -> Self {
+        ScaffoldError::MachineFailure(err)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobotOrientation {
+    Up,
+    Down,
+    Left,
+    Right,
+    Tumbling,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VacuumRobot {
+    pub position: Position,
+    pub orientation: RobotOrientation,
+}
+
+pub struct ScaffoldView {
+    scaffold: HashSet<Position>,
+    robot: Option<VacuumRobot>,
+    width: isize,
+    height: isize,
+}
+
+impl ScaffoldView {
+    pub fn parse(image: &str) -> Result<Self, ScaffoldError> {
+        let mut scaffold = HashSet::new();
+        let mut robot = None;
+        let mut width = 0;
+        let mut height = 0;
+
+        for (y, line) in image.lines().filter(|line| !line.is_empty()).enumerate() {
+            let y = y as isize;
+            for (x, symbol) in line.chars().enumerate() {
+                let position = (x as isize, y);
+                let orientation = match symbol {
+                    '.' => continue,
+                    '#' => {
+                        scaffold.insert(position);
+                        continue;
+                    }
+                    '^' => RobotOrientation::Up,
+                    'v' => RobotOrientation::Down,
+                    '<' => RobotOrientation::Left,
+                    '>' => RobotOrientation::Right,
+                    'X' => RobotOrientation::Tumbling,
+                    _ => return Err(ScaffoldError::InvalidSymbol(symbol)),
+                };
+
+                // unless it's tumbling through space, robot is always standing on the scaffold
+                if orientation != RobotOrientation::Tumbling {
+                    scaffold.insert(position);
+                }
+                robot = Some(VacuumRobot {
+                    position,
+                    orientation,
+                });
+            }
+            width = width.max(line.len() as isize);
+            height = y + 1;
+        }
+
+        Ok(ScaffoldView {
+            scaffold,
+            robot,
+            width,
+            height,
+        })
+    }
+
+    pub fn width(&self) -> isize {
+        self.width
+    }
+
+    pub fn height(&self) -> isize {
+        self.height
+    }
+
+    pub fn robot(&self) -> Option<VacuumRobot> {
+        self.robot
+    }
+
+    pub fn is_scaffold(&self, position: Position) -> bool {
+        self.scaffold.contains(&position)
+    }
+
+    pub fn intersections(&self) -> Vec<Position> {
+        let mut intersections: Vec<_> = self
+            .scaffold
+            .iter()
+            .cloned()
+            .filter(|&(x, y)| {
+                [(x, y - 1), (x, y + 1), (x - 1, y), (x + 1, y)]
+                    .iter()
+                    .all(|&neighbour| self.is_scaffold(neighbour))
+            })
+            .collect();
+
+        intersections.sort_by_key(|&(x, y)| (y, x));
+        intersections
+    }
+
+    pub fn alignment_parameters_sum(&self) -> isize {
+        self.intersections().iter().map(|&(x, y)| x * y).sum()
+    }
+}
+
+// runs the camera program to completion and parses whatever it has drawn
+pub fn capture(tape: Tape) -> Result<ScaffoldView, ScaffoldError> {
+    let mut camera = AsciiMachine::new(tape);
+    match camera.run()? {
+        StepOutcome::Halted => ScaffoldView::parse(&camera.take_text()),
+        _ => Err(ScaffoldError::UnexpectedInputRequest),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE_IMAGE: &str = "\
+..#..........
+..#..........
+#######...###
+#.#...#...#.#
+#############
+..#...#...#..
+..#####...^..
+";
+
+    // camera that simply prints the given image
+    fn camera_tape(image: &str) -> Tape {
+        let mut values: Vec<_> = image.bytes().flat_map(|b| vec![104, b as isize]).collect();
+        values.push(99);
+        Tape::new(values)
+    }
+
+    #[test]
+    fn example_intersections_are_found() {
+        let view = ScaffoldView::parse(EXAMPLE_IMAGE).unwrap();
+        assert_eq!(vec![(2, 2), (2, 4), (6, 4), (10, 4)], view.intersections());
+    }
+
+    #[test]
+    fn example_alignment_parameters_sum_to_76() {
+        let view = ScaffoldView::parse(EXAMPLE_IMAGE).unwrap();
+        assert_eq!(76, view.alignment_parameters_sum());
+    }
+
+    #[test]
+    fn camera_output_is_captured_and_parsed() {
+        let view = capture(camera_tape(EXAMPLE_IMAGE)).unwrap();
+
+        assert_eq!(13, view.width());
+        assert_eq!(7, view.height());
+        assert_eq!(
+            Some(VacuumRobot {
+                position: (10, 6),
+                orientation: RobotOrientation::Up
+            }),
+            view.robot()
+        );
+        assert!(view.is_scaffold((10, 6)));
+        assert_eq!(76, view.alignment_parameters_sum());
+    }
+
+    #[test]
+    fn invalid_camera_output_is_rejected() {
+        match capture(camera_tape("#?#\n")) {
+            Err(ScaffoldError::InvalidSymbol('?')) => (),
+            other => panic!("expected invalid symbol, got {:?}", other.map(|_| ())),
+        }
+        match capture(Tape::new(vec![3, 0, 99])) {
+            Err(ScaffoldError::UnexpectedInputRequest) => (),
+            other => panic!("expected input request, got {:?}", other.map(|_| ())),
+        }
+    }
+}
@@ -0,0 +1,273 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::instructions::InstructionSet;
+use crate::intcode_machine::{
+    AccessKind, IntcodeMachine, IntcodeMachineError, ParamMode, StepOutcome, Tape, ValueQueue,
+    HALT_OP_CODE, JMP_FALSE_OP_CODE, JMP_TRUE_OP_CODE,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Termination {
+    Halted,
+    AwaitingInput,
+    BudgetExhausted,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CodeWrite {
+    pub step: usize,
+    // address of the instruction that performed the write
+    pub writer: usize,
+    pub target: usize,
+    pub previous: isize,
+    pub value: isize,
+    pub previously_executed: bool,
+    pub in_code_region: bool,
+}
+
+#[derive(Debug)]
+pub struct SelfModificationReport {
+    code_region: BTreeSet<usize>,
+    writes: Vec<CodeWrite>,
+    termination: Termination,
+}
+
+impl SelfModificationReport {
+    pub fn code_region(&self) -> &BTreeSet<usize> {
+        &self.code_region
+    }
+
+    pub fn writes(&self) -> &[CodeWrite] {
+        &self.writes
+    }
+
+    pub fn termination(&self) -> Termination {
+        self.termination
+    }
+
+    pub fn is_self_modifying(&self) -> bool {
+        !self.writes.is_empty()
+    }
+}
+
+impl fmt::Display for SelfModificationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} writes into code ({} statically decoded cells), run ended with {:?}",
+            self.writes.len(),
+            self.code_region.len(),
+            self.termination
+        )?;
+
+        for write in self.writes.iter() {
+            let mut reasons = Vec::new();
+            if write.previously_executed {
+                reasons.push("executed");
+            }
+            if write.in_code_region {
+                reasons.push("code region");
+            }
+            writeln!(
+                f,
+                "step {}: instruction at {} wrote {} over {} at {} ({})",
+                write.step,
+                write.writer,
+                write.value,
+                write.previous,
+                write.target,
+                reasons.join(", ")
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// Cells reachable from the start of the program by following the fall through path and
+// jumps with immediate targets. Jumps to computed targets can't be followed statically.
+pub fn decode_code_region(tape: &[isize]) -> BTreeSet<usize> {
+    let instructions = InstructionSet::standard();
+    let mut code_region = BTreeSet::new();
+    let mut visited = BTreeSet::new();
+    let mut pending = vec![0];
+
+    while let Some(address) = pending.pop() {
+        if address >= tape.len() || !visited.insert(address) {
+            continue;
+        }
+        let decoded = match instructions.decode(tape[address]) {
+            Some(decoded) => decoded,
+            None => continue,
+        };
+
+        let next_instruction = address + 1 + decoded.param_modes.len();
+        if next_instruction > tape.len() {
+            continue;
+        }
+        code_region.extend(address..next_instruction);
+
+        match decoded.op_code {
+            HALT_OP_CODE => continue,
+            JMP_TRUE_OP_CODE | JMP_FALSE_OP_CODE
+                if decoded.param_modes[1] == ParamMode::Immediate && tape[address + 2] >= 0 =>
+            {
+                pending.push(tape[address + 2] as usize);
+            }
+            _ => (),
+        }
+        pending.push(next_instruction);
+    }
+
+    code_region
+}
+
+// Runs the program for at most `budget` steps, reporting every write into a cell that either
+// got executed before or belongs to the statically decoded code region.
+pub fn analyse(
+    tape: Tape,
+    inputs: Vec<isize>,
+    budget: usize,
+) -> Result<SelfModificationReport, IntcodeMachineError> {
+    let code_region = decode_code_region(tape.as_slice());
+    let mut executed = BTreeSet::new();
+    let mut writes = Vec::new();
+
+    let mut machine = IntcodeMachine::new(tape, ValueQueue::from(inputs), ValueQueue::new());
+    machine.enable_tracing();
+
+    let mut termination = Termination::BudgetExhausted;
+    for step in 0..budget {
+        let outcome = machine.step()?;
+
+        for access in machine.take_trace() {
+            match access.kind {
+                AccessKind::Execute => {
+                    executed.insert(access.address);
+                }
+                AccessKind::Write { previous, value } => {
+                    let previously_executed = executed.contains(&access.address);
+                    let in_code_region = code_region.contains(&access.address);
+                    if previously_executed || in_code_region {
+                        writes.push(CodeWrite {
+                            step,
+                            writer: access.instruction,
+                            target: access.address,
+                            previous,
+                            value,
+                            previously_executed,
+                            in_code_region,
+                        });
+                    }
+                }
+                AccessKind::Read => (),
+            }
+        }
+
+        match outcome {
+            StepOutcome::Executed => (),
+            StepOutcome::Halted => {
+                termination = Termination::Halted;
+                break;
+            }
+            StepOutcome::AwaitingInput => {
+                termination = Termination::AwaitingInput;
+                break;
+            }
+        }
+    }
+
+    Ok(SelfModificationReport {
+        code_region,
+        writes,
+        termination,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn code_region_follows_immediate_jumps() {
+        // data cells at 3 and 7 are jumped over, so the halt is only reachable through a jump
+        let tape = vec![1105, 1, 4, 42, 1106, 0, 8, 77, 99];
+        let code_region: Vec<_> = decode_code_region(&tape).into_iter().collect();
+
+        assert_eq!(vec![0, 1, 2, 4, 5, 6, 8], code_region);
+    }
+
+    #[test]
+    fn writes_into_executed_instructions_are_reported() {
+        let tape = Tape::new(vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50]);
+        let report = analyse(tape, vec![], 100).unwrap();
+
+        assert_eq!(Termination::Halted, report.termination());
+        assert_eq!(
+            vec![
+                CodeWrite {
+                    step: 0,
+                    writer: 0,
+                    target: 3,
+                    previous: 3,
+                    value: 70,
+                    previously_executed: false,
+                    in_code_region: true,
+                },
+                CodeWrite {
+                    step: 1,
+                    writer: 4,
+                    target: 0,
+                    previous: 1,
+                    value: 3500,
+                    previously_executed: true,
+                    in_code_region: true,
+                },
+            ],
+            report.writes()
+        );
+        assert_eq!(
+            "2 writes into code (9 statically decoded cells), run ended with Halted\n\
+             step 0: instruction at 0 wrote 70 over 3 at 3 (code region)\n\
+             step 1: instruction at 4 wrote 3500 over 1 at 0 (executed, code region)\n",
+            report.to_string()
+        );
+    }
+
+    #[test]
+    fn writes_into_data_are_not_reported() {
+        // reads a value, doubles it into the data cell and prints it
+        let tape = Tape::new(vec![3, 9, 1, 9, 9, 9, 4, 9, 99, 0]);
+        let report = analyse(tape, vec![21], 100).unwrap();
+
+        assert_eq!(Termination::Halted, report.termination());
+        assert!(!report.is_self_modifying());
+    }
+
+    #[test]
+    fn analysis_stops_when_running_out_of_input_or_budget() {
+        let tape = vec![3, 9, 1, 9, 9, 9, 4, 9, 99, 0];
+        let report = analyse(Tape::new(tape.clone()), vec![], 100).unwrap();
+        assert_eq!(Termination::AwaitingInput, report.termination());
+
+        let report = analyse(Tape::new(tape), vec![1], 2).unwrap();
+        assert_eq!(Termination::BudgetExhausted, report.termination());
+    }
+
+    #[test]
+    fn day2_program_modifies_its_own_code() {
+        let mut day2_tape = Tape::new(utils::read_input_file("day2.input"));
+        day2_tape.write(1, 12);
+        day2_tape.write(2, 2);
+
+        let report = analyse(day2_tape, vec![], 1000).unwrap();
+        assert_eq!(Termination::Halted, report.termination());
+        assert!(report.is_self_modifying());
+        assert!(report
+            .writes()
+            .iter()
+            .all(|write| write.in_code_region || write.previously_executed));
+    }
+}
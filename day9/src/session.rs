@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+
+use crate::ascii::{AsciiError, AsciiMachine};
+use crate::intcode_machine::{IntcodeMachineError, State, StepOutcome, Tape};
+use crate::memory_view::{MemoryDiff, MemoryView};
+
+const META_COMMAND_PREFIX: char = ':';
+
+const HELP: &str = "\
+:save <name>       save current machine state under given name
+:restore <name>    restore previously saved state
+:replay <path>     feed every line of the file as if it was typed in
+:history <path>    write all lines sent to the machine so far into the file
+:dump              print machine memory
+:diff <name>       show memory changes since the state was saved
+:help              print this message
+:quit              leave the session
+";
+
+#[derive(Debug)]
+pub enum SessionError {
+    MachineFailure(IntcodeMachineError),
+    MachineHalted,
+    InvalidLine(AsciiError),
+    UnknownCommand(String),
+    MissingArgument(String),
+    UnknownSnapshot(String),
+    NestedReplay(String),
+    // output of the lines replayed before the one that failed
+    ReplayInterrupted {
+        output: String,
+        cause: Box<SessionError>,
+    },
+    IoFailure(io::Error),
+}
+
+impl From<IntcodeMachineError> for SessionError {
+    fn from(err: IntcodeMachineError) -> Self {
+        SessionError::MachineFailure(err)
+    }
+}
+
+impl From<AsciiError> for SessionError {
+    fn from(err: AsciiError) -> Self {
+        SessionError::InvalidLine(err)
+    }
+}
+
+impl From<io::Error> for SessionError {
+    fn from(err: io::Error) -> Self {
+        SessionError::IoFailure(err)
+    }
+}
+
+struct Snapshot {
+    state: State,
+    halted: bool,
+    // so that after restoring, the history is still a valid replay of what happened
+    history_len: usize,
+}
+
+pub struct Session {
+    machine: AsciiMachine,
+    snapshots: HashMap<String, Snapshot>,
+    history: Vec<String>,
+    halted: bool,
+    // replayed files cannot replay other files, so that they can't end up replaying themselves
+    replaying: bool,
+}
+
+impl Session {
+    pub fn new(tape: Tape) -> Self {
+        Session {
+            machine: AsciiMachine::new(tape),
+            snapshots: HashMap::new(),
+            history: Vec::new(),
+            halted: false,
+            replaying: false,
+        }
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted
+    }
+
+    pub fn history(&self) -> &[String] {
+        &self.history
+    }
+
+    // runs the program until it asks for the first input and returns whatever it printed
+    pub fn start(&mut self) -> Result<String, SessionError> {
+        self.resume()
+    }
+
+    pub fn handle_line(&mut self, line: &str) -> Result<String, SessionError> {
+        if line.starts_with(META_COMMAND_PREFIX) {
+            self.handle_meta_command(&line[1..])
+        } else {
+            self.send(line)
+        }
+    }
+
+    fn resume(&mut self) -> Result<String, SessionError> {
+        let outcome = self.machine.run()?;
+        self.halted = outcome == StepOutcome::Halted;
+        Ok(self.machine.take_text())
+    }
+
+    fn send(&mut self, line: &str) -> Result<String, SessionError> {
+        if self.halted {
+            return Err(SessionError::MachineHalted);
+        }
+
+        self.machine.send_line(line)?;
+        let output = self.resume()?;
+        // failing lines are not recorded, so that replaying the history doesn't fail again
+        self.history.push(line.to_string());
+        Ok(output)
+    }
+
+    fn handle_meta_command(&mut self, command: &str) -> Result<String, SessionError> {
+        let mut words = command.split_whitespace();
+        let name = words.next().unwrap_or("");
+        let argument = words.next();
+
+        let require_argument =
+            || argument.ok_or_else(|| SessionError::MissingArgument(name.to_string()));
+
+        match name {
+            "save" => self.save(require_argument()?),
+            "restore" => self.restore(require_argument()?),
+            "replay" => self.replay(require_argument()?),
+            "history" => self.write_history(require_argument()?),
+            "dump" => Ok(dump_memory(&self.machine.dump_state())),
+            "diff" => self.diff(require_argument()?),
+            "help" => Ok(HELP.to_string()),
+            _ => Err(SessionError::UnknownCommand(name.to_string())),
+        }
+    }
+
+    fn save(&mut self, name: &str) -> Result<String, SessionError> {
+        let snapshot = Snapshot {
+            state: self.machine.dump_state(),
+            halted: self.halted,
+            history_len: self.history.len(),
+        };
+        self.snapshots.insert(name.to_string(), snapshot);
+
+        Ok(format!("saved state as '{}'\n", name))
+    }
+
+    fn restore(&mut self, name: &str) -> Result<String, SessionError> {
+        let snapshot = self
+            .snapshots
+            .get(name)
+            .ok_or_else(|| SessionError::UnknownSnapshot(name.to_string()))?;
+
+        self.machine = AsciiMachine::load_state(snapshot.state.clone());
+        self.history.truncate(snapshot.history_len);
+        self.halted = snapshot.halted;
+
+        Ok(format!("restored state '{}'\n", name))
+    }
+
+    fn diff(&self, name: &str) -> Result<String, SessionError> {
+        let snapshot = self
+            .snapshots
+            .get(name)
+            .ok_or_else(|| SessionError::UnknownSnapshot(name.to_string()))?;
+
+        let current = self.machine.dump_state();
+        let diff = MemoryDiff::new(&snapshot.state, &current);
+        Ok(diff.to_string())
+    }
+
+    fn replay(&mut self, path: &str) -> Result<String, SessionError> {
+        if self.replaying {
+            return Err(SessionError::NestedReplay(path.to_string()));
+        }
+        let contents = fs::read_to_string(path)?;
+
+        self.replaying = true;
+        let output = self.replay_lines(&contents);
+        self.replaying = false;
+        output
+    }
+
+    fn replay_lines(&mut self, contents: &str) -> Result<String, SessionError> {
+        let mut output = String::new();
+        for line in contents.lines() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match self.handle_line(line) {
+                Ok(line_output) => output.push_str(&line_output),
+                Err(err) => {
+                    return Err(SessionError::ReplayInterrupted {
+                        output,
+                        cause: Box::new(err),
+                    })
+                }
+            }
+        }
+
+        Ok(output)
+    }
+
+    fn write_history(&self, path: &str) -> Result<String, SessionError> {
+        let contents: String = self
+            .history
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect();
+        fs::write(path, contents)?;
+
+        Ok(format!(
+            "written {} lines to '{}'\n",
+            self.history.len(),
+            path
+        ))
+    }
+}
+
+pub fn dump_memory(state: &State) -> String {
+    MemoryView::new(state).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // reads a line and prints it back, while counting lines at address 50; halts after 3 lines
+    fn counting_echo_tape() -> Tape {
+        Tape::new(vec![
+            3, 51, 4, 51, 1008, 51, 10, 52, 1006, 52, 0, 1001, 50, 1, 50, 1008, 50, 3, 52, 1006,
+            52, 0, 99,
+        ])
+    }
+
+    #[test]
+    fn lines_are_forwarded_and_echoed_back() {
+        let mut session = Session::new(counting_echo_tape());
+        assert_eq!("", session.start().unwrap());
+
+        assert_eq!("north\n", session.handle_line("north").unwrap());
+        assert_eq!("take lamp\n", session.handle_line("take lamp").unwrap());
+        assert!(!session.is_halted());
+        assert_eq!("south\n", session.handle_line("south").unwrap());
+        assert!(session.is_halted());
+
+        match session.handle_line("west") {
+            Err(SessionError::MachineHalted) => (),
+            other => panic!("expected machine to have halted, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn restoring_state_rewinds_machine_and_history() {
+        let mut session = Session::new(counting_echo_tape());
+        session.start().unwrap();
+
+        session.handle_line("north").unwrap();
+        session.handle_line(":save checkpoint").unwrap();
+        session.handle_line("east").unwrap();
+        session.handle_line("east").unwrap();
+        assert!(session.is_halted());
+
+        session.handle_line(":restore checkpoint").unwrap();
+        assert!(!session.is_halted());
+        assert_eq!(&["north".to_string()], session.history());
+
+        session.handle_line("west").unwrap();
+        assert!(!session.is_halted());
+    }
+
+    #[test]
+    fn restoring_state_saved_after_halting_keeps_machine_halted() {
+        let mut session = Session::new(counting_echo_tape());
+        session.start().unwrap();
+
+        for line in ["north", "east", "south"] {
+            session.handle_line(line).unwrap();
+        }
+        session.handle_line(":save end").unwrap();
+        session.handle_line(":restore end").unwrap();
+        assert!(session.is_halted());
+
+        match session.handle_line("west") {
+            Err(SessionError::MachineHalted) => (),
+            other => panic!("expected machine to have halted, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn non_ascii_lines_are_rejected() {
+        let mut session = Session::new(counting_echo_tape());
+        session.start().unwrap();
+
+        match session.handle_line("take crème brûlée") {
+            Err(SessionError::InvalidLine(_)) => (),
+            other => panic!("expected invalid line, got {:?}", other),
+        }
+        assert!(session.history().is_empty());
+        assert_eq!("north\n", session.handle_line("north").unwrap());
+    }
+
+    #[test]
+    fn lines_the_machine_failed_on_are_not_recorded() {
+        // reads a single character and then hits an invalid instruction
+        let mut session = Session::new(Tape::new(vec![3, 5, 98]));
+        session.start().unwrap();
+
+        match session.handle_line("north") {
+            Err(SessionError::MachineFailure(_)) => (),
+            other => panic!("expected machine failure, got {:?}", other),
+        }
+        assert!(session.history().is_empty());
+    }
+
+    #[test]
+    fn replayed_file_cannot_replay_other_files() {
+        let path = std::env::temp_dir().join(format!("day9_replay_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+        std::fs::write(path, format!("north\n:replay {}\nsouth\n", path)).unwrap();
+
+        let mut session = Session::new(counting_echo_tape());
+        session.start().unwrap();
+        let result = session.handle_line(&format!(":replay {}", path));
+        std::fs::remove_file(path).unwrap();
+
+        match result {
+            Err(SessionError::ReplayInterrupted { output, cause }) => {
+                assert_eq!("north\n", output);
+                match *cause {
+                    SessionError::NestedReplay(nested) => assert_eq!(path, nested),
+                    other => panic!("expected nested replay, got {:?}", other),
+                }
+            }
+            other => panic!("expected interrupted replay, got {:?}", other),
+        }
+        assert_eq!(&["north".to_string()], session.history());
+
+        // once the replay is over, files can be replayed again
+        match session.handle_line(&format!(":replay {}", path)) {
+            Err(SessionError::IoFailure(_)) => (),
+            other => panic!("expected missing file, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn history_can_be_written_and_replayed() {
+        let path = std::env::temp_dir().join(format!("day9_session_{}", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        let mut session = Session::new(counting_echo_tape());
+        session.start().unwrap();
+        session.handle_line("north").unwrap();
+        session.handle_line("take lamp").unwrap();
+        session.handle_line(&format!(":history {}", path)).unwrap();
+
+        let mut replayed = Session::new(counting_echo_tape());
+        replayed.start().unwrap();
+        let output = replayed.handle_line(&format!(":replay {}", path)).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        assert_eq!("north\ntake lamp\n", output);
+        assert_eq!(session.history(), replayed.history());
+    }
+
+    #[test]
+    fn memory_dump_includes_registers_and_addresses() {
+        let state = State::new_from_tape(Tape::new((0..12).collect()));
+        assert_eq!(
+            "head: 0, relative base: 0\n     \
+             0: [<0>]     1     2     3     4     5     6     7     8     9\n    \
+             10:    10    11\n",
+            dump_memory(&state)
+        );
+    }
+
+    #[test]
+    fn memory_changes_since_snapshot_are_shown() {
+        let mut session = Session::new(counting_echo_tape());
+        session.start().unwrap();
+        session.handle_line(":save start").unwrap();
+        assert_eq!("no changes\n", session.handle_line(":diff start").unwrap());
+
+        session.handle_line("hi").unwrap();
+        // last character read was the newline, while the line counter went up by one
+        assert_eq!(
+            "    50: 0 -> 1\n    51: 0 -> 10\n",
+            session.handle_line(":diff start").unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_meta_commands_are_reported() {
+        let mut session = Session::new(counting_echo_tape());
+
+        match session.handle_line(":fly") {
+            Err(SessionError::UnknownCommand(command)) => assert_eq!("fly", command),
+            other => panic!("expected unknown command, got {:?}", other),
+        }
+        match session.handle_line(":save") {
+            Err(SessionError::MissingArgument(command)) => assert_eq!("save", command),
+            other => panic!("expected missing argument, got {:?}", other),
+        }
+        match session.handle_line(":restore nowhere") {
+            Err(SessionError::UnknownSnapshot(name)) => assert_eq!("nowhere", name),
+            other => panic!("expected unknown snapshot, got {:?}", other),
+        }
+    }
+}
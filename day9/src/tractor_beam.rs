@@ -0,0 +1,162 @@
+use crate::intcode_machine::{IntcodeMachine, IntcodeMachineError, StepOutcome, Tape, ValueQueue};
+
+const STATIONARY: isize = 0;
+const PULLED: isize = 1;
+
+pub type Position = (isize, isize);
+
+#[derive(Debug)]
+pub enum BeamError {
+    MachineFailure(IntcodeMachineError),
+    InvalidReading(isize),
+    MissingReading,
+}
+
+impl From<IntcodeMachineError> for BeamError {
+    fn from(err: IntcodeMachineError) -> Self {
+        BeamError::MachineFailure(err)
+    }
+}
+
+pub struct TractorBeam {
+    // drone program can only be used once, so every probe runs on a fork of this one
+    // (which still ends up copying the whole program, as soon as it writes anything)
+    base: IntcodeMachine<ValueQueue, ValueQueue>,
+}
+
+impl TractorBeam {
+    pub fn new(tape: Tape) -> Self {
+        TractorBeam {
+            base: IntcodeMachine::new(tape, ValueQueue::new(), ValueQueue::new()),
+        }
+    }
+
+    pub fn is_pulled(&self, (x, y): Position) -> Result<bool, BeamError> {
+        let mut drone = self.base.fork();
+        drone.input_mut().push(x);
+        drone.input_mut().push(y);
+
+        loop {
+            match drone.step()? {
+                StepOutcome::Executed => {
+                    if let Some(reading) = drone.output_mut().pop() {
+                        return match reading {
+                            STATIONARY => Ok(false),
+                            PULLED => Ok(true),
+                            _ => Err(BeamError::InvalidReading(reading)),
+                        };
+                    }
+                }
+                _ => return Err(BeamError::MissingReading),
+            }
+        }
+    }
+
+    // number of points affected by the beam in the size x size area closest to the emitter
+    pub fn count_affected(&self, size: isize) -> Result<usize, BeamError> {
+        let mut count = 0;
+        for y in 0..size {
+            for x in 0..size {
+                if self.is_pulled((x, y))? {
+                    count += 1;
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    // Finds the top-left corner of the closest square of given size fitting entirely within
+    // the beam, without looking beyond `limit` in either direction.
+    // Rather than checking every point, it walks along the left edge of the beam, row by row,
+    // treating current row as the bottom of the square and only checking the opposite corner.
+    // This relies on the beam's edges moving away from the emitter monotonically.
+    pub fn find_square(&self, size: isize, limit: isize) -> Result<Option<Position>, BeamError> {
+        if size <= 0 {
+            return Ok(None);
+        }
+
+        // any further to the right and the square would stick out beyond the limit
+        let last_column = limit - size + 1;
+        // beam's left edge never moves back, so no row needs to be scanned from the start
+        let mut left_edge = 0;
+        for bottom in size - 1..=limit {
+            // close to the emitter the beam might be so narrow that some rows are empty
+            let mut x = left_edge;
+            while x <= last_column && !self.is_pulled((x, bottom))? {
+                x += 1;
+            }
+            if x > last_column {
+                continue;
+            }
+            left_edge = x;
+
+            let top = bottom - size + 1;
+            if self.is_pulled((x + size - 1, top))? {
+                return Ok(Some((x, top)));
+            }
+        }
+
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // beam covering all points with x <= y <= 2x
+    fn beam_tape() -> Tape {
+        Tape::new(vec![
+            3, 30, 3, 31, 7, 31, 30, 32, 1002, 30, 2, 33, 7, 33, 31, 34, 1, 32, 34, 35, 1008, 35,
+            0, 36, 4, 36, 99, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ])
+    }
+
+    #[test]
+    fn single_points_are_probed_independently() {
+        let beam = TractorBeam::new(beam_tape());
+
+        assert!(beam.is_pulled((0, 0)).unwrap());
+        assert!(beam.is_pulled((3, 5)).unwrap());
+        assert!(!beam.is_pulled((3, 7)).unwrap());
+        assert!(!beam.is_pulled((5, 3)).unwrap());
+        // probes must not affect each other
+        assert!(beam.is_pulled((3, 5)).unwrap());
+    }
+
+    #[test]
+    fn affected_points_are_counted() {
+        let beam = TractorBeam::new(beam_tape());
+        assert_eq!(30, beam.count_affected(10).unwrap());
+    }
+
+    #[test]
+    fn closest_fitting_square_is_found() {
+        let beam = TractorBeam::new(beam_tape());
+
+        assert_eq!(Some((0, 0)), beam.find_square(1, 100).unwrap());
+        assert_eq!(Some((4, 6)), beam.find_square(3, 100).unwrap());
+        assert_eq!(Some((18, 27)), beam.find_square(10, 100).unwrap());
+        assert_eq!(None, beam.find_square(10, 20).unwrap());
+    }
+
+    #[test]
+    fn square_must_fit_within_limit_horizontally() {
+        // same beam as above mirrored along the diagonal, covering all points with y <= x <= 2y
+        let mut cells = beam_tape().as_slice().to_vec();
+        cells.swap(1, 3);
+        let beam = TractorBeam::new(Tape::new(cells));
+
+        assert_eq!(Some((6, 4)), beam.find_square(3, 8).unwrap());
+        assert_eq!(None, beam.find_square(3, 7).unwrap());
+    }
+
+    #[test]
+    fn invalid_readings_are_rejected() {
+        let beam = TractorBeam::new(Tape::new(vec![3, 10, 3, 10, 104, 5, 99]));
+        match beam.is_pulled((1, 1)) {
+            Err(BeamError::InvalidReading(5)) => (),
+            other => panic!("expected invalid reading, got {:?}", other),
+        }
+    }
+}
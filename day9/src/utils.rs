@@ -1,5 +1,4 @@
 use itertools::Itertools;
-use std::fs;
 
 pub fn num_to_digits_vec(val: usize) -> Vec<usize> {
     let mut digits = Vec::new();
@@ -13,7 +12,7 @@ pub fn num_to_digits_vec(val: usize) -> Vec<usize> {
     digits
 }
 
-pub fn digits_vec_to_num(digits: &Vec<usize>) -> usize {
+pub fn digits_vec_to_num(digits: &[usize]) -> usize {
     digits
         .iter()
         .cloned()
@@ -24,7 +23,7 @@ pub fn digits_vec_to_num(digits: &Vec<usize>) -> usize {
         .unwrap()
 }
 
-pub fn utf8_dec_num_repr_to_num(utf8_dec_digits: &Vec<u8>) -> isize {
+pub fn utf8_dec_num_repr_to_num(utf8_dec_digits: &[u8]) -> isize {
     let mut possible_sign = utf8_dec_digits.iter().peekable();
     if possible_sign.peek().unwrap() == &&45 {
         0 - digits_vec_to_num(
@@ -32,27 +31,31 @@ pub fn utf8_dec_num_repr_to_num(utf8_dec_digits: &Vec<u8>) -> isize {
                 .iter()
                 .skip(1)
                 .map(|&d| (d - 48) as usize)
-                .collect(),
+                .collect::<Vec<_>>(),
         ) as isize
     } else {
-        digits_vec_to_num(&utf8_dec_digits.iter().map(|&d| (d - 48) as usize).collect()) as isize
+        digits_vec_to_num(
+            &utf8_dec_digits
+                .iter()
+                .map(|&d| (d - 48) as usize)
+                .collect::<Vec<_>>(),
+        ) as isize
     }
 }
 
-pub fn parse_multiple_utf8_num_repr_lns(utf8_dec_digits_nums: &Vec<u8>) -> Vec<isize> {
+pub fn parse_multiple_utf8_num_repr_lns(utf8_dec_digits_nums: &[u8]) -> Vec<isize> {
     utf8_dec_digits_nums
         .split(|d| d == &10)
         .filter(|ds| !ds.is_empty())
-        .map(|ds| utf8_dec_num_repr_to_num(&ds.to_vec()))
+        .map(utf8_dec_num_repr_to_num)
         .collect()
 }
 
 pub fn read_input_file(path: &str) -> Vec<isize> {
-    fs::read_to_string(path)
-        .unwrap()
-        .split(',')
-        .map(|s| s.parse::<isize>().unwrap())
-        .collect()
+    match intcode_loader::load_program(path) {
+        Ok(program) => program,
+        Err(err) => panic!("could not load {}: {}", path, err),
+    }
 }
 
 #[cfg(test)]
@@ -62,12 +65,12 @@ mod tests {
 
     #[test]
     fn utf8_dec_num_repr_to_num_works_for_positive_values() {
-        assert_eq!(42, utf8_dec_num_repr_to_num(&vec![52, 50]))
+        assert_eq!(42, utf8_dec_num_repr_to_num(&[52, 50]))
     }
 
     #[test]
     fn utf8_dec_num_repr_to_num_works_for_negative_values() {
-        assert_eq!(-42, utf8_dec_num_repr_to_num(&vec![45, 52, 50]))
+        assert_eq!(-42, utf8_dec_num_repr_to_num(&[45, 52, 50]))
     }
 
     #[test]
@@ -0,0 +1,367 @@
+use std::fmt::Write as FmtWrite;
+
+use crate::intcode_machine::Tape;
+
+pub const IMPORT_MODULE: &str = "intcode";
+pub const INPUT_IMPORT: &str = "input";
+pub const OUTPUT_IMPORT: &str = "output";
+pub const RUN_EXPORT: &str = "run";
+pub const MEMORY_EXPORT: &str = "memory";
+
+const CELL_SIZE: usize = 8;
+const PAGE_SIZE: usize = 65536;
+// 32 bit memories can't grow beyond 4GiB
+const MAX_CELLS: usize = PAGE_SIZE * PAGE_SIZE / CELL_SIZE;
+
+// Helpers shared by every module. Cells are stored as little endian i64s, cell n at byte 8n,
+// and the memory grows on demand like the tape does. Anything the machine would reject, such
+// as an unknown opcode or a negative address, traps with `unreachable`.
+const RUNTIME: &str = r#"
+  (global $relative_base (mut i64) (i64.const 0))
+
+  ;; byte offset of the cell, growing the memory if the cell lies beyond it
+  (func $cell (param $address i64) (result i32)
+    (local $pages i64)
+    (if (i64.ge_u (local.get $address) (i64.const MAX_CELLS))
+      (then (unreachable)))
+    (local.set $pages
+      (i64.add (i64.div_u (local.get $address) (i64.const CELLS_PER_PAGE)) (i64.const 1)))
+    (if (i64.gt_u (local.get $pages) (i64.extend_i32_u (memory.size)))
+      (then
+        (if (i32.eq
+              (memory.grow
+                (i32.wrap_i64 (i64.sub (local.get $pages) (i64.extend_i32_u (memory.size)))))
+              (i32.const -1))
+          (then (unreachable)))))
+    (return (i32.wrap_i64 (i64.mul (local.get $address) (i64.const 8)))))
+
+  (func $load (param $address i64) (result i64)
+    (return (i64.load (call $cell (local.get $address)))))
+
+  (func $store (param $address i64) (param $value i64)
+    (i64.store (call $cell (local.get $address)) (local.get $value)))
+
+  ;; mode digit of the parameter selected by the divisor (100, 1000 or 10000)
+  (func $mode (param $instruction i64) (param $divisor i64) (result i64)
+    (return
+      (i64.rem_u (i64.div_u (local.get $instruction) (local.get $divisor)) (i64.const 10))))
+
+  (func $param_address (param $instruction i64) (param $position i64) (param $divisor i64)
+    (result i64)
+    (local $mode i64)
+    (local.set $mode (call $mode (local.get $instruction) (local.get $divisor)))
+    (if (i64.eq (local.get $mode) (i64.const 0))
+      (then (return (call $load (local.get $position)))))
+    (if (i64.eq (local.get $mode) (i64.const 1))
+      (then (return (local.get $position))))
+    (if (i64.eq (local.get $mode) (i64.const 2))
+      (then
+        (return (i64.add (call $load (local.get $position)) (global.get $relative_base)))))
+    (unreachable))
+
+  (func $read (param $instruction i64) (param $position i64) (param $divisor i64) (result i64)
+    (return
+      (call $load
+        (call $param_address
+          (local.get $instruction) (local.get $position) (local.get $divisor)))))
+
+  (func $write (param $instruction i64) (param $position i64) (param $divisor i64)
+    (param $value i64)
+    (if (i64.eq (call $mode (local.get $instruction) (local.get $divisor)) (i64.const 1))
+      (then (unreachable)))
+    (call $store
+      (call $param_address (local.get $instruction) (local.get $position) (local.get $divisor))
+      (local.get $value)))
+"#;
+
+// Dispatch loop of the exported function, returning the cell 0 once the program halts.
+// The br_table jumps out of the block of the opcode, right in front of its implementation,
+// opcodes beyond the table end up in the invalid block.
+const DISPATCH: &str = r#"
+  (func (export "run") (result i64)
+    (local $ip i64)
+    (local $instruction i64)
+    (local $op_code i64)
+    (local $target i64)
+    (loop $dispatch
+      (local.set $instruction (call $load (local.get $ip)))
+      (if (i64.lt_s (local.get $instruction) (i64.const 0))
+        (then (unreachable)))
+      (local.set $op_code (i64.rem_u (local.get $instruction) (i64.const 100)))
+      (if (i64.eq (local.get $op_code) (i64.const 99))
+        (then (return (call $load (i64.const 0)))))
+      (block $relative_base_offset
+      (block $equals
+      (block $less_than
+      (block $jump_if_false
+      (block $jump_if_true
+      (block $output
+      (block $input
+      (block $multiply
+      (block $add
+      (block $invalid
+        (br_table $invalid $add $multiply $input $output $jump_if_true $jump_if_false
+          $less_than $equals $relative_base_offset $invalid
+          (i32.wrap_i64 (local.get $op_code)))
+      ) ;; invalid
+        (unreachable)
+      ) ;; add
+        (call $write (local.get $instruction) (i64.add (local.get $ip) (i64.const 3))
+          (i64.const 10000)
+          (i64.add
+            (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 1))
+              (i64.const 100))
+            (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 2))
+              (i64.const 1000))))
+        (local.set $ip (i64.add (local.get $ip) (i64.const 4)))
+        (br $dispatch)
+      ) ;; multiply
+        (call $write (local.get $instruction) (i64.add (local.get $ip) (i64.const 3))
+          (i64.const 10000)
+          (i64.mul
+            (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 1))
+              (i64.const 100))
+            (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 2))
+              (i64.const 1000))))
+        (local.set $ip (i64.add (local.get $ip) (i64.const 4)))
+        (br $dispatch)
+      ) ;; input
+        (call $write (local.get $instruction) (i64.add (local.get $ip) (i64.const 1))
+          (i64.const 100)
+          (call $input))
+        (local.set $ip (i64.add (local.get $ip) (i64.const 2)))
+        (br $dispatch)
+      ) ;; output
+        (call $output
+          (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 1))
+            (i64.const 100)))
+        (local.set $ip (i64.add (local.get $ip) (i64.const 2)))
+        (br $dispatch)
+      ) ;; jump if true
+        (local.set $target
+          (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 2))
+            (i64.const 1000)))
+        (if (i64.ne
+              (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 1))
+                (i64.const 100))
+              (i64.const 0))
+          (then
+            (local.set $ip (local.get $target))
+            (br $dispatch)))
+        (local.set $ip (i64.add (local.get $ip) (i64.const 3)))
+        (br $dispatch)
+      ) ;; jump if false
+        (local.set $target
+          (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 2))
+            (i64.const 1000)))
+        (if (i64.eq
+              (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 1))
+                (i64.const 100))
+              (i64.const 0))
+          (then
+            (local.set $ip (local.get $target))
+            (br $dispatch)))
+        (local.set $ip (i64.add (local.get $ip) (i64.const 3)))
+        (br $dispatch)
+      ) ;; less than
+        (call $write (local.get $instruction) (i64.add (local.get $ip) (i64.const 3))
+          (i64.const 10000)
+          (i64.extend_i32_u
+            (i64.lt_s
+              (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 1))
+                (i64.const 100))
+              (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 2))
+                (i64.const 1000)))))
+        (local.set $ip (i64.add (local.get $ip) (i64.const 4)))
+        (br $dispatch)
+      ) ;; equals
+        (call $write (local.get $instruction) (i64.add (local.get $ip) (i64.const 3))
+          (i64.const 10000)
+          (i64.extend_i32_u
+            (i64.eq
+              (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 1))
+                (i64.const 100))
+              (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 2))
+                (i64.const 1000)))))
+        (local.set $ip (i64.add (local.get $ip) (i64.const 4)))
+        (br $dispatch)
+      ) ;; relative base offset
+        (global.set $relative_base
+          (i64.add
+            (global.get $relative_base)
+            (call $read (local.get $instruction) (i64.add (local.get $ip) (i64.const 1))
+              (i64.const 100))))
+        (local.set $ip (i64.add (local.get $ip) (i64.const 2)))
+        (br $dispatch))
+    (unreachable))
+"#;
+
+fn data_segment(tape: &[isize]) -> String {
+    let mut data = String::with_capacity(tape.len() * CELL_SIZE * 3);
+    for &cell in tape {
+        for byte in (cell as i64).to_le_bytes().iter() {
+            write!(data, "\\{:02x}", byte).unwrap();
+        }
+    }
+    data
+}
+
+// Text of a module running the program on its own. The host has to provide
+// `intcode.input: [] -> [i64]` and `intcode.output: [i64] -> []`, the program is started by
+// calling the exported `run` and the tape can be inspected through the exported memory.
+pub fn to_wat(tape: &Tape) -> String {
+    let cells = tape.as_slice();
+    let pages = (cells.len() * CELL_SIZE).div_ceil(PAGE_SIZE).max(1);
+
+    let mut module = String::new();
+    writeln!(module, "(module").unwrap();
+    writeln!(
+        module,
+        "  (import \"{}\" \"{}\" (func $input (result i64)))",
+        IMPORT_MODULE, INPUT_IMPORT
+    )
+    .unwrap();
+    writeln!(
+        module,
+        "  (import \"{}\" \"{}\" (func $output (param i64)))",
+        IMPORT_MODULE, OUTPUT_IMPORT
+    )
+    .unwrap();
+    writeln!(
+        module,
+        "  (memory (export \"{}\") {})",
+        MEMORY_EXPORT, pages
+    )
+    .unwrap();
+    writeln!(module, "  (data (i32.const 0) \"{}\")", data_segment(cells)).unwrap();
+    module.push_str(
+        &RUNTIME
+            .replace("MAX_CELLS", &MAX_CELLS.to_string())
+            .replace("CELLS_PER_PAGE", &(PAGE_SIZE / CELL_SIZE).to_string()),
+    );
+    module.push_str(&DISPATCH.replace("\"run\"", &format!("\"{}\"", RUN_EXPORT)));
+    writeln!(module, ")").unwrap();
+
+    module
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intcode_machine::{IntcodeMachine, ValueQueue};
+    use crate::utils;
+    use crate::wat_interpreter::Module;
+
+    // value of the cell 0 and all of the outputs, or None if the program failed
+    fn run_machine(tape: &[isize], inputs: &[isize]) -> Option<(isize, Vec<isize>)> {
+        let mut machine = IntcodeMachine::new(
+            Tape::new(tape.to_vec()),
+            ValueQueue::from(inputs.to_vec()),
+            ValueQueue::new(),
+        );
+        let result = machine.run().ok()?;
+        Some((result, machine.output_mut().drain()))
+    }
+
+    fn run_wat(tape: &[isize], inputs: &[isize]) -> Option<(isize, Vec<isize>)> {
+        let module = Module::parse(&to_wat(&Tape::new(tape.to_vec()))).unwrap();
+        let mut inputs = inputs.iter();
+        let mut outputs = Vec::new();
+
+        let result = module
+            .invoke(
+                RUN_EXPORT,
+                &[],
+                &mut |name: &str, args: &[i64]| match name {
+                    INPUT_IMPORT => inputs
+                        .next()
+                        .map(|&value| Some(value as i64))
+                        .ok_or_else(|| "out of input".to_string()),
+                    OUTPUT_IMPORT => {
+                        outputs.push(args[0] as isize);
+                        Ok(None)
+                    }
+                    other => Err(format!("unknown import {}", other)),
+                },
+            )
+            .ok()?;
+        Some((result? as isize, outputs))
+    }
+
+    fn assert_same_results(tape: &[isize], inputs: &[isize]) {
+        let expected = run_machine(tape, inputs);
+        assert!(expected.is_some(), "sample program has to halt");
+        assert_eq!(expected, run_wat(tape, inputs));
+    }
+
+    #[test]
+    fn generated_module_is_valid_wasm() {
+        let tape = Tape::new(utils::read_input_file("day9.input"));
+        let binary = wat::parse_str(to_wat(&tape)).unwrap();
+
+        wasmparser::Validator::new().validate_all(&binary).unwrap();
+    }
+
+    #[test]
+    fn module_declares_imports_exports_and_tape_data() {
+        let module = to_wat(&Tape::new(vec![104, -1, 99]));
+
+        assert!(module.contains("(import \"intcode\" \"input\" (func $input (result i64)))"));
+        assert!(module.contains("(import \"intcode\" \"output\" (func $output (param i64)))"));
+        assert!(module.contains("(memory (export \"memory\") 1)"));
+        assert!(module.contains("(func (export \"run\") (result i64)"));
+        assert!(module.contains("(loop $dispatch"));
+        assert!(module.contains(
+            "(data (i32.const 0) \"\
+             \\68\\00\\00\\00\\00\\00\\00\\00\
+             \\ff\\ff\\ff\\ff\\ff\\ff\\ff\\ff\
+             \\63\\00\\00\\00\\00\\00\\00\\00\")"
+        ));
+    }
+
+    #[test]
+    fn memory_is_large_enough_for_the_tape() {
+        let tape = Tape::new(vec![0; PAGE_SIZE / CELL_SIZE + 1]);
+
+        assert!(to_wat(&tape).contains("(memory (export \"memory\") 2)"));
+    }
+
+    #[test]
+    fn results_match_machine_on_samples() {
+        // day2 arithmetic
+        assert_same_results(&[1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50], &[]);
+        // day5 comparisons and jumps in both position and immediate mode
+        let compare_to_8 = [
+            3, 21, 1008, 21, 8, 20, 1005, 20, 22, 107, 8, 21, 20, 1006, 20, 31, 1106, 0, 36, 98, 0,
+            0, 1002, 21, 125, 20, 4, 20, 1105, 1, 46, 104, 999, 1105, 1, 46, 1101, 1000, 1, 20, 4,
+            20, 1105, 1, 46, 98, 99,
+        ];
+        for input in 7..=9 {
+            assert_same_results(&compare_to_8, &[input]);
+        }
+        // day9 quine writes far beyond the initial tape using the relative base
+        assert_same_results(
+            &[
+                109, 1, 204, -1, 1001, 100, 1, 100, 1008, 100, 16, 101, 1006, 101, 0, 99,
+            ],
+            &[],
+        );
+        assert_same_results(&[104, 1_125_899_906_842_624, 99], &[]);
+    }
+
+    #[test]
+    fn results_match_machine_on_day9_boost_test_mode() {
+        let tape = utils::read_input_file("day9.input");
+
+        assert_same_results(&tape, &[1]);
+    }
+
+    #[test]
+    fn invalid_programs_trap() {
+        // unknown opcode, negative address and write in immediate mode
+        for tape in [vec![42, 99], vec![4, -1, 99], vec![11101, 1, 1, 0, 99]] {
+            assert_eq!(None, run_machine(&tape, &[]));
+            assert_eq!(None, run_wat(&tape, &[]));
+        }
+    }
+}
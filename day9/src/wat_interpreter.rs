@@ -0,0 +1,571 @@
+// Tree walking interpreter for the subset of the text format emitted by the wasm backend:
+// folded instructions over i64 and i32 values, named locals, globals and labels, a single
+// memory and imported functions provided by the host. i32 values are kept sign extended.
+use std::collections::HashMap;
+
+const PAGE_SIZE: usize = 65536;
+const MAX_PAGES: usize = 65536;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Sexpr {
+    Atom(String),
+    Bytes(Vec<u8>),
+    List(Vec<Sexpr>),
+}
+
+impl Sexpr {
+    fn atom(&self) -> Option<&str> {
+        match self {
+            Sexpr::Atom(atom) => Some(atom),
+            _ => None,
+        }
+    }
+
+    fn list(&self) -> Option<&[Sexpr]> {
+        match self {
+            Sexpr::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    // first atom of a list, such as `func` in `(func $name ...)`
+    fn head(&self) -> Option<&str> {
+        self.list()
+            .and_then(|items| items.first())
+            .and_then(Sexpr::atom)
+    }
+}
+
+fn parse_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    loop {
+        match chars.next() {
+            Some('"') => return Ok(bytes),
+            Some('\\') => {
+                let escaped: String = chars.by_ref().take(2).collect();
+                let byte = u8::from_str_radix(&escaped, 16)
+                    .map_err(|_| format!("unsupported escape '\\{}'", escaped))?;
+                bytes.push(byte);
+            }
+            Some(c) => {
+                let mut buffer = [0; 4];
+                bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            }
+            None => return Err("unterminated string".to_string()),
+        }
+    }
+}
+
+fn parse_sexpr(source: &str) -> Result<Sexpr, String> {
+    let mut chars = source.chars().peekable();
+    let mut stack: Vec<Vec<Sexpr>> = vec![Vec::new()];
+
+    while let Some(c) = chars.next() {
+        match c {
+            ';' if chars.peek() == Some(&';') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' => stack.push(Vec::new()),
+            ')' => {
+                let list = stack.pop().unwrap();
+                stack
+                    .last_mut()
+                    .ok_or_else(|| "unbalanced ')'".to_string())?
+                    .push(Sexpr::List(list));
+            }
+            '"' => {
+                let bytes = parse_string(&mut chars)?;
+                stack.last_mut().unwrap().push(Sexpr::Bytes(bytes));
+            }
+            c if c.is_whitespace() => (),
+            c => {
+                let mut atom = c.to_string();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || next == '(' || next == ')' {
+                        break;
+                    }
+                    atom.push(next);
+                    chars.next();
+                }
+                stack.last_mut().unwrap().push(Sexpr::Atom(atom));
+            }
+        }
+    }
+
+    match (stack.pop(), stack.is_empty()) {
+        (Some(mut top), true) if top.len() == 1 => Ok(top.remove(0)),
+        _ => Err("expected a single top level expression".to_string()),
+    }
+}
+
+fn parse_int(atom: &str) -> Result<i64, String> {
+    atom.parse()
+        .map_err(|_| format!("invalid integer '{}'", atom))
+}
+
+struct Function {
+    params: Vec<String>,
+    locals: Vec<String>,
+    body: Vec<Sexpr>,
+}
+
+pub(crate) struct Module {
+    functions: HashMap<String, Function>,
+    // function name -> imported field
+    imports: HashMap<String, String>,
+    exports: HashMap<String, String>,
+    pages: usize,
+    data: Vec<(usize, Vec<u8>)>,
+    globals: Vec<(String, Sexpr)>,
+}
+
+// (func $name (export "name") (param $a i64) (result i64) (local $b i64) body...)
+fn parse_function(
+    items: &[Sexpr],
+    anonymous: usize,
+) -> Result<(String, Option<String>, Function), String> {
+    let mut name = format!("${}", anonymous);
+    let mut export = None;
+    let mut function = Function {
+        params: Vec::new(),
+        locals: Vec::new(),
+        body: Vec::new(),
+    };
+
+    for item in items.iter().skip(1) {
+        match (item.atom(), item.head()) {
+            (Some(atom), _) if atom.starts_with('$') => name = atom.to_string(),
+            (_, Some("export")) => match &item.list().unwrap()[1] {
+                Sexpr::Bytes(bytes) => export = Some(String::from_utf8_lossy(bytes).into_owned()),
+                _ => return Err("invalid export".to_string()),
+            },
+            (_, Some("param")) => function.params.push(
+                item.list().unwrap()[1]
+                    .atom()
+                    .unwrap_or_default()
+                    .to_string(),
+            ),
+            (_, Some("local")) => function.locals.push(
+                item.list().unwrap()[1]
+                    .atom()
+                    .unwrap_or_default()
+                    .to_string(),
+            ),
+            (_, Some("result")) => (),
+            _ => function.body.push(item.clone()),
+        }
+    }
+
+    Ok((name, export, function))
+}
+
+impl Module {
+    pub(crate) fn parse(source: &str) -> Result<Self, String> {
+        let root = parse_sexpr(source)?;
+        let fields = match (root.head(), root.list()) {
+            (Some("module"), Some(items)) => &items[1..],
+            _ => return Err("expected a module".to_string()),
+        };
+
+        let mut module = Module {
+            functions: HashMap::new(),
+            imports: HashMap::new(),
+            exports: HashMap::new(),
+            pages: 0,
+            data: Vec::new(),
+            globals: Vec::new(),
+        };
+
+        for (index, field) in fields.iter().enumerate() {
+            let items = field.list().unwrap_or_default();
+            match field.head() {
+                // (import "module" "field" (func $name ...))
+                Some("import") => {
+                    let name = match (&items[2], items[3].list()) {
+                        (Sexpr::Bytes(name), Some(func)) => (
+                            String::from_utf8_lossy(name).into_owned(),
+                            func[1].atom().unwrap_or_default().to_string(),
+                        ),
+                        _ => return Err("unsupported import".to_string()),
+                    };
+                    module.imports.insert(name.1, name.0);
+                }
+                // (memory (export "name") pages)
+                Some("memory") => {
+                    module.pages =
+                        parse_int(items.last().and_then(Sexpr::atom).unwrap_or(""))? as usize
+                }
+                // (data (i32.const offset) "bytes")
+                Some("data") => match (items[1].list(), &items[2]) {
+                    (Some(offset), Sexpr::Bytes(bytes)) => {
+                        let offset = parse_int(offset[1].atom().unwrap_or(""))? as usize;
+                        module.data.push((offset, bytes.clone()));
+                    }
+                    _ => return Err("unsupported data segment".to_string()),
+                },
+                // (global $name (mut i64) (init))
+                Some("global") => module.globals.push((
+                    items[1].atom().unwrap_or_default().to_string(),
+                    items.last().unwrap().clone(),
+                )),
+                Some("func") => {
+                    let (name, export, function) = parse_function(items, index)?;
+                    if let Some(export) = export {
+                        module.exports.insert(export, name.clone());
+                    }
+                    module.functions.insert(name, function);
+                }
+                other => return Err(format!("unsupported module field {:?}", other)),
+            }
+        }
+
+        Ok(module)
+    }
+
+    // Instantiates the module and calls the exported function. Imports are resolved by their
+    // field name, returning an error from the host traps.
+    pub(crate) fn invoke<H>(
+        &self,
+        export: &str,
+        args: &[i64],
+        host: &mut H,
+    ) -> Result<Option<i64>, String>
+    where
+        H: FnMut(&str, &[i64]) -> Result<Option<i64>, String>,
+    {
+        let mut instance = Instance {
+            module: self,
+            host,
+            memory: vec![0; self.pages * PAGE_SIZE],
+            globals: HashMap::new(),
+        };
+        for (offset, bytes) in self.data.iter() {
+            instance
+                .memory
+                .get_mut(*offset..*offset + bytes.len())
+                .ok_or_else(|| "data segment does not fit in memory".to_string())?
+                .copy_from_slice(bytes);
+        }
+        for (name, init) in self.globals.iter() {
+            let value = instance
+                .value(init, &mut HashMap::new())
+                .map_err(Signal::into_trap)?;
+            instance.globals.insert(name.clone(), value);
+        }
+
+        let function = self
+            .exports
+            .get(export)
+            .ok_or_else(|| format!("unknown export {}", export))?;
+        instance
+            .call(function, args.to_vec())
+            .map_err(Signal::into_trap)
+    }
+}
+
+enum Signal {
+    Branch(String),
+    Return(Option<i64>),
+    Trap(String),
+}
+
+impl Signal {
+    fn into_trap(self) -> String {
+        match self {
+            Signal::Trap(message) => message,
+            Signal::Branch(label) => format!("branch to unknown label {}", label),
+            Signal::Return(_) => "unexpected return".to_string(),
+        }
+    }
+}
+
+fn trap<T>(message: String) -> Result<T, Signal> {
+    Err(Signal::Trap(message))
+}
+
+struct Instance<'a, H> {
+    module: &'a Module,
+    host: &'a mut H,
+    memory: Vec<u8>,
+    globals: HashMap<String, i64>,
+}
+
+type Locals = HashMap<String, i64>;
+
+impl<'a, H> Instance<'a, H>
+where
+    H: FnMut(&str, &[i64]) -> Result<Option<i64>, String>,
+{
+    fn call(&mut self, name: &str, args: Vec<i64>) -> Result<Option<i64>, Signal> {
+        if let Some(field) = self.module.imports.get(name) {
+            return (self.host)(field, &args).or_else(trap);
+        }
+
+        let module = self.module;
+        let function = match module.functions.get(name) {
+            Some(function) => function,
+            None => return trap(format!("unknown function {}", name)),
+        };
+        let mut locals: Locals = function.params.iter().cloned().zip(args).collect();
+        for local in function.locals.iter() {
+            locals.insert(local.clone(), 0);
+        }
+
+        match self.sequence(&function.body, &mut locals) {
+            Ok(value) | Err(Signal::Return(value)) => Ok(value),
+            Err(signal) => Err(signal),
+        }
+    }
+
+    // value of the last expression
+    fn sequence(&mut self, nodes: &[Sexpr], locals: &mut Locals) -> Result<Option<i64>, Signal> {
+        let mut last = None;
+        for node in nodes {
+            last = self.eval(node, locals)?;
+        }
+        Ok(last)
+    }
+
+    fn value(&mut self, node: &Sexpr, locals: &mut Locals) -> Result<i64, Signal> {
+        match self.eval(node, locals)? {
+            Some(value) => Ok(value),
+            None => trap(format!("expected a value from {:?}", node.head())),
+        }
+    }
+
+    fn operands(&mut self, nodes: &[Sexpr], locals: &mut Locals) -> Result<Vec<i64>, Signal> {
+        nodes.iter().map(|node| self.value(node, locals)).collect()
+    }
+
+    fn address(&self, address: i64, len: usize) -> Result<usize, Signal> {
+        let address = address as u32 as usize;
+        if address + len > self.memory.len() {
+            return trap(format!("out of bounds memory access at {}", address));
+        }
+        Ok(address)
+    }
+
+    // body of a block or a loop, skipping its label
+    fn labelled(items: &[Sexpr]) -> (Option<&str>, &[Sexpr]) {
+        match items.get(1).and_then(Sexpr::atom) {
+            Some(label) if label.starts_with('$') => (Some(label), &items[2..]),
+            _ => (None, &items[1..]),
+        }
+    }
+
+    fn eval(&mut self, node: &Sexpr, locals: &mut Locals) -> Result<Option<i64>, Signal> {
+        let items = match node.list() {
+            Some(items) => items,
+            None => return trap(format!("unsupported plain instruction {:?}", node)),
+        };
+        let instruction = node.head().unwrap_or_default();
+        let immediate = || items.get(1).and_then(Sexpr::atom).unwrap_or_default();
+
+        let value = match instruction {
+            "i64.const" | "i32.const" => parse_int(immediate()).or_else(trap)?,
+            "local.get" => match locals.get(immediate()) {
+                Some(&value) => value,
+                None => return trap(format!("unknown local {}", immediate())),
+            },
+            "local.set" => {
+                let value = self.value(&items[2], locals)?;
+                locals.insert(immediate().to_string(), value);
+                return Ok(None);
+            }
+            "global.get" => match self.globals.get(immediate()) {
+                Some(&value) => value,
+                None => return trap(format!("unknown global {}", immediate())),
+            },
+            "global.set" => {
+                let value = self.value(&items[2], locals)?;
+                self.globals.insert(immediate().to_string(), value);
+                return Ok(None);
+            }
+            "call" => {
+                let args = self.operands(&items[2..], locals)?;
+                return self.call(immediate(), args);
+            }
+            "block" => {
+                let (label, body) = Self::labelled(items);
+                return match self.sequence(body, locals) {
+                    Err(Signal::Branch(target)) if Some(target.as_str()) == label => Ok(None),
+                    result => result.map(|_| None),
+                };
+            }
+            "loop" => {
+                let (label, body) = Self::labelled(items);
+                loop {
+                    match self.sequence(body, locals) {
+                        Err(Signal::Branch(target)) if Some(target.as_str()) == label => (),
+                        result => return result.map(|_| None),
+                    }
+                }
+            }
+            "if" => {
+                let mut condition = None;
+                let mut branch = None;
+                for item in items[1..].iter() {
+                    match item.head() {
+                        Some("then") if condition != Some(0) => branch = Some(item),
+                        Some("else") if condition == Some(0) => branch = Some(item),
+                        Some("then") | Some("else") => (),
+                        _ => condition = Some(self.value(item, locals)? as i32),
+                    }
+                }
+                if let Some(branch) = branch {
+                    self.sequence(&branch.list().unwrap()[1..], locals)?;
+                }
+                return Ok(None);
+            }
+            "br" => return Err(Signal::Branch(immediate().to_string())),
+            "br_table" => {
+                let labels: Vec<_> = items[1..items.len() - 1]
+                    .iter()
+                    .filter_map(Sexpr::atom)
+                    .collect();
+                let index = self.value(items.last().unwrap(), locals)? as u32 as usize;
+                let label = labels.get(index).unwrap_or(labels.last().unwrap());
+                return Err(Signal::Branch(label.to_string()));
+            }
+            "return" => {
+                let value = match items.get(1) {
+                    Some(item) => Some(self.value(item, locals)?),
+                    None => None,
+                };
+                return Err(Signal::Return(value));
+            }
+            "unreachable" => return trap("unreachable executed".to_string()),
+            "memory.size" => (self.memory.len() / PAGE_SIZE) as i64,
+            "memory.grow" => {
+                let pages = self.value(&items[1], locals)? as u32 as usize;
+                let current = self.memory.len() / PAGE_SIZE;
+                if current + pages > MAX_PAGES {
+                    -1
+                } else {
+                    self.memory.resize((current + pages) * PAGE_SIZE, 0);
+                    current as i64
+                }
+            }
+            "i64.load" => {
+                let address = self.value(&items[1], locals)?;
+                let address = self.address(address, 8)?;
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(&self.memory[address..address + 8]);
+                i64::from_le_bytes(bytes)
+            }
+            "i64.store" => {
+                let operands = self.operands(&items[1..], locals)?;
+                let address = self.address(operands[0], 8)?;
+                self.memory[address..address + 8].copy_from_slice(&operands[1].to_le_bytes());
+                return Ok(None);
+            }
+            "i32.wrap_i64" => self.value(&items[1], locals)? as i32 as i64,
+            "i64.extend_i32_u" => self.value(&items[1], locals)? as u32 as i64,
+            _ => {
+                let operands = self.operands(&items[1..], locals)?;
+                if operands.len() != 2 {
+                    return trap(format!("unsupported instruction {}", instruction));
+                }
+                let (a, b) = (operands[0], operands[1]);
+                match instruction {
+                    "i64.add" => a.wrapping_add(b),
+                    "i64.sub" => a.wrapping_sub(b),
+                    "i64.mul" => a.wrapping_mul(b),
+                    "i64.div_u" | "i64.rem_u" if b == 0 => {
+                        return trap("integer divide by zero".to_string())
+                    }
+                    "i64.div_u" => ((a as u64) / (b as u64)) as i64,
+                    "i64.rem_u" => ((a as u64) % (b as u64)) as i64,
+                    "i64.eq" | "i32.eq" => (a == b) as i64,
+                    "i64.ne" => (a != b) as i64,
+                    "i64.lt_s" => (a < b) as i64,
+                    "i64.gt_u" => ((a as u64) > (b as u64)) as i64,
+                    "i64.ge_u" => ((a as u64) >= (b as u64)) as i64,
+                    _ => return trap(format!("unsupported instruction {}", instruction)),
+                }
+            }
+        };
+
+        Ok(Some(value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_imports(name: &str, _: &[i64]) -> Result<Option<i64>, String> {
+        Err(format!("unexpected import {}", name))
+    }
+
+    #[test]
+    fn loops_and_locals_are_interpreted() {
+        let module = Module::parse(
+            r#"(module
+                 (func (export "select") (param $index i64) (result i64)
+                   (block $two
+                     (block $one
+                       (block $zero
+                         (br_table $zero $one $two (i32.wrap_i64 (local.get $index))))
+                       (return (i64.const 10)))
+                     (return (i64.const 11)))
+                   (return (i64.const 12)))
+                 (func (export "count") (result i64)
+                   (local $i i64) (local $sum i64)
+                   (loop $next
+                     (local.set $i (i64.add (local.get $i) (i64.const 1)))
+                     (local.set $sum (i64.add (local.get $sum) (local.get $i)))
+                     (if (i64.ne (local.get $i) (i64.const 10))
+                       (then (br $next))))
+                   (return (local.get $sum))))"#,
+        )
+        .unwrap();
+
+        assert_eq!(Ok(Some(11)), module.invoke("select", &[1], &mut no_imports));
+        // indices beyond the table take the default label
+        assert_eq!(Ok(Some(12)), module.invoke("select", &[7], &mut no_imports));
+        assert_eq!(Ok(Some(55)), module.invoke("count", &[], &mut no_imports));
+    }
+
+    #[test]
+    fn memory_is_initialised_from_data_and_grows() {
+        let module = Module::parse(
+            r#"(module
+                 (memory 1)
+                 (data (i32.const 8) "\2a\00\00\00\00\00\00\00")
+                 (func (export "grow") (result i64)
+                   (if (i32.eq (memory.grow (i32.const 1)) (i32.const 1))
+                     (then (i64.store (i32.const 65536) (i64.load (i32.const 8)))))
+                   (return (i64.load (i32.const 65536)))))"#,
+        )
+        .unwrap();
+
+        assert_eq!(Ok(Some(42)), module.invoke("grow", &[], &mut no_imports));
+    }
+
+    #[test]
+    fn traps_are_reported() {
+        let module = Module::parse(
+            r#"(module
+                 (import "env" "fail" (func $fail (result i64)))
+                 (memory 1)
+                 (func (export "unreachable") (unreachable))
+                 (func (export "out_of_bounds") (result i64) (i64.load (i32.const 65536)))
+                 (func (export "host") (result i64) (call $fail)))"#,
+        )
+        .unwrap();
+
+        assert!(module.invoke("unreachable", &[], &mut no_imports).is_err());
+        assert!(module
+            .invoke("out_of_bounds", &[], &mut no_imports)
+            .is_err());
+        assert_eq!(
+            Err("unexpected import fail".to_string()),
+            module.invoke("host", &[], &mut no_imports)
+        );
+    }
+}
@@ -0,0 +1,236 @@
+use day9::intcode_machine::{
+    ADD_OP_CODE, EQUALS_OP_CODE, HALT_OP_CODE, IMMEDIATE_MODE, INPUT_OP_CODE, JMP_FALSE_OP_CODE,
+    JMP_TRUE_OP_CODE, LESS_THAN_OP_CODE, MUL_OP_CODE, OUTPUT_OP_CODE, POSITION_MODE, RELATIVE_MODE,
+    RLT_BASE_OFFSET_OP_CODE,
+};
+
+use crate::rng::Rng;
+
+const IMMEDIATE_VALUE_RANGE: isize = 20;
+// how far past the end of the tape an occasional out of range address can point to
+const OUT_OF_RANGE_SPREAD: isize = 3;
+
+#[derive(Debug, Clone)]
+pub struct FuzzConfig {
+    pub instructions: usize,
+    pub data_cells: usize,
+    pub inputs: usize,
+    pub budget: usize,
+    // day5 and day7 interpreters know nothing about relative mode, nor the base offset opcode
+    pub relative_base: bool,
+    // one in how many addresses deliberately points outside the tape; 0 disables it
+    pub out_of_range_rate: u64,
+}
+
+impl Default for FuzzConfig {
+    fn default() -> Self {
+        FuzzConfig {
+            instructions: 12,
+            data_cells: 8,
+            inputs: 4,
+            budget: 1000,
+            relative_base: false,
+            out_of_range_rate: 20,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FuzzCase {
+    pub tape: Vec<isize>,
+    pub inputs: Vec<isize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Param {
+    Read,
+    Write,
+    JumpTarget,
+}
+
+pub(crate) fn params(op_code: isize) -> &'static [Param] {
+    use Param::*;
+
+    match op_code {
+        ADD_OP_CODE | MUL_OP_CODE | LESS_THAN_OP_CODE | EQUALS_OP_CODE => &[Read, Read, Write],
+        JMP_TRUE_OP_CODE | JMP_FALSE_OP_CODE => &[Read, JumpTarget],
+        INPUT_OP_CODE => &[Write],
+        OUTPUT_OP_CODE | RLT_BASE_OFFSET_OP_CODE => &[Read],
+        _ => &[],
+    }
+}
+
+struct Generator<'a> {
+    rng: &'a mut Rng,
+    config: &'a FuzzConfig,
+    tape_len: isize,
+    instruction_starts: Vec<isize>,
+}
+
+impl<'a> Generator<'a> {
+    fn address(&mut self) -> isize {
+        if self.config.out_of_range_rate > 0 && self.rng.one_in(self.config.out_of_range_rate) {
+            self.tape_len + self.rng.range(0, OUT_OF_RANGE_SPREAD)
+        } else {
+            self.rng.range(0, self.tape_len)
+        }
+    }
+
+    fn mode(&mut self, param: Param) -> usize {
+        let mut modes = vec![POSITION_MODE];
+        if param != Param::Write {
+            modes.push(IMMEDIATE_MODE);
+        }
+        if self.config.relative_base {
+            modes.push(RELATIVE_MODE);
+        }
+        *self.rng.choose(&modes)
+    }
+
+    fn operand(&mut self, param: Param, mode: usize) -> isize {
+        match (param, mode) {
+            // relative base stays small, so relative offsets are treated just like addresses
+            (_, POSITION_MODE) | (_, RELATIVE_MODE) => self.address(),
+            (Param::JumpTarget, _) => *self.rng.choose(&self.instruction_starts),
+            _ => self
+                .rng
+                .range(-IMMEDIATE_VALUE_RANGE, IMMEDIATE_VALUE_RANGE + 1),
+        }
+    }
+
+    fn instruction(&mut self, op_code: isize) -> Vec<isize> {
+        let params = params(op_code);
+        let modes: Vec<_> = params.iter().map(|&param| self.mode(param)).collect();
+
+        let encoded_modes = modes
+            .iter()
+            .rev()
+            .fold(0, |acc, &mode| acc * 10 + mode as isize);
+        let mut instruction = vec![encoded_modes * 100 + op_code];
+        for (&param, &mode) in params.iter().zip(modes.iter()) {
+            instruction.push(self.operand(param, mode));
+        }
+
+        instruction
+    }
+}
+
+pub fn generate(rng: &mut Rng, config: &FuzzConfig) -> FuzzCase {
+    let mut op_codes = vec![
+        ADD_OP_CODE,
+        MUL_OP_CODE,
+        INPUT_OP_CODE,
+        OUTPUT_OP_CODE,
+        JMP_TRUE_OP_CODE,
+        JMP_FALSE_OP_CODE,
+        LESS_THAN_OP_CODE,
+        EQUALS_OP_CODE,
+    ];
+    if config.relative_base {
+        op_codes.push(RLT_BASE_OFFSET_OP_CODE);
+    }
+
+    // layout has to be known upfront so that operands can refer to any part of the tape
+    let mut program: Vec<_> = (0..config.instructions)
+        .map(|_| *rng.choose(&op_codes))
+        .collect();
+    program.push(HALT_OP_CODE);
+
+    let mut instruction_starts = Vec::new();
+    let mut code_len = 0;
+    for &op_code in program.iter() {
+        instruction_starts.push(code_len);
+        code_len += 1 + params(op_code).len() as isize;
+    }
+
+    let mut generator = Generator {
+        rng,
+        config,
+        tape_len: code_len + config.data_cells as isize,
+        instruction_starts,
+    };
+
+    let mut tape: Vec<_> = program
+        .into_iter()
+        .flat_map(|op_code| generator.instruction(op_code))
+        .collect();
+    for _ in 0..config.data_cells {
+        tape.push(
+            generator
+                .rng
+                .range(-IMMEDIATE_VALUE_RANGE, IMMEDIATE_VALUE_RANGE + 1),
+        );
+    }
+
+    let inputs = (0..config.inputs)
+        .map(|_| {
+            generator
+                .rng
+                .range(-IMMEDIATE_VALUE_RANGE, IMMEDIATE_VALUE_RANGE + 1)
+        })
+        .collect();
+
+    FuzzCase { tape, inputs }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // walks the tape assuming nothing got modified yet
+    fn decode_op_codes(tape: &[isize]) -> Vec<isize> {
+        let mut op_codes = Vec::new();
+        let mut position = 0;
+        loop {
+            let op_code = tape[position] % 100;
+            op_codes.push(op_code);
+            if op_code == HALT_OP_CODE {
+                return op_codes;
+            }
+            position += 1 + params(op_code).len();
+        }
+    }
+
+    #[test]
+    fn generated_tape_decodes_into_requested_number_of_instructions() {
+        let config = FuzzConfig::default();
+        let mut rng = Rng::new(7);
+
+        for _ in 0..50 {
+            let case = generate(&mut rng, &config);
+            let op_codes = decode_op_codes(&case.tape);
+
+            assert_eq!(config.instructions + 1, op_codes.len());
+            assert_eq!(config.inputs, case.inputs.len());
+            assert!(!op_codes.contains(&RLT_BASE_OFFSET_OP_CODE));
+        }
+    }
+
+    #[test]
+    fn write_parameters_are_never_in_immediate_mode() {
+        let config = FuzzConfig {
+            relative_base: true,
+            ..FuzzConfig::default()
+        };
+        let mut rng = Rng::new(3);
+
+        for _ in 0..50 {
+            let case = generate(&mut rng, &config);
+            let mut position = 0;
+            loop {
+                let instruction = case.tape[position];
+                let op_code = instruction % 100;
+                if op_code == HALT_OP_CODE {
+                    break;
+                }
+
+                let params = params(op_code);
+                let write_mode = instruction / 10_isize.pow(params.len() as u32 + 1) % 10;
+                if params.last() == Some(&Param::Write) {
+                    assert_ne!(IMMEDIATE_MODE as isize, write_mode);
+                }
+                position += 1 + params.len();
+            }
+        }
+    }
+}
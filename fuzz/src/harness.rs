@@ -0,0 +1,384 @@
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+
+use crate::generator::{self, FuzzCase, FuzzConfig};
+use crate::rng::Rng;
+
+// day9 grows its tape up to any address it touches, so a single large address would
+// simply make the fuzzer run out of memory
+const DAY9_MEMORY_LIMIT: isize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Interpreter {
+    Day5,
+    Day7,
+    Day9,
+}
+
+pub const ALL_INTERPRETERS: [Interpreter; 3] =
+    [Interpreter::Day5, Interpreter::Day7, Interpreter::Day9];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    TapeOutOfBounds,
+    ExecutionFailure,
+    InputFailure,
+    Panic,
+    MemoryLimit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Termination {
+    Halted(isize),
+    BudgetExhausted,
+    Failed(ErrorKind),
+}
+
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub termination: Termination,
+    pub outputs: Vec<isize>,
+    pub memory: Vec<isize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DivergenceKind {
+    Termination,
+    Outputs,
+    Memory,
+}
+
+#[derive(Debug, Clone)]
+pub struct Divergence {
+    pub case: FuzzCase,
+    pub kinds: Vec<DivergenceKind>,
+    pub executions: Vec<(Interpreter, Execution)>,
+}
+
+// shared I/O for all of the interpreters, so that none of them has to parse text
+#[derive(Default)]
+struct FuzzIo {
+    inputs: VecDeque<isize>,
+    outputs: Vec<isize>,
+}
+
+impl day5::intcode_machine::MachineInput for FuzzIo {
+    fn read_value(&mut self) -> Option<isize> {
+        self.inputs.pop_front()
+    }
+}
+
+impl day5::intcode_machine::MachineOutput for FuzzIo {
+    fn write_value(&mut self, value: isize) {
+        self.outputs.push(value)
+    }
+}
+
+impl day7::intcode_machine::MachineInput for FuzzIo {
+    fn read_value(&mut self) -> Option<isize> {
+        self.inputs.pop_front()
+    }
+}
+
+impl day7::intcode_machine::MachineOutput for FuzzIo {
+    fn write_value(&mut self, value: isize) {
+        self.outputs.push(value)
+    }
+}
+
+impl day9::intcode_machine::MachineInput for FuzzIo {
+    fn read_value(&mut self) -> Option<isize> {
+        self.inputs.pop_front()
+    }
+}
+
+impl day9::intcode_machine::MachineOutput for FuzzIo {
+    fn write_value(&mut self, value: isize) {
+        self.outputs.push(value)
+    }
+}
+
+// Runs `step` until it reports termination or the budget runs out. Interpreters are allowed
+// to panic on malformed programs, which is recorded rather than propagated.
+fn drive<F>(budget: usize, mut step: F) -> Termination
+where
+    F: FnMut() -> Result<Option<isize>, ErrorKind>,
+{
+    for _ in 0..budget {
+        match panic::catch_unwind(AssertUnwindSafe(&mut step)) {
+            Ok(Ok(None)) => (),
+            Ok(Ok(Some(result))) => return Termination::Halted(result),
+            Ok(Err(kind)) => return Termination::Failed(kind),
+            Err(_) => return Termination::Failed(ErrorKind::Panic),
+        }
+    }
+    Termination::BudgetExhausted
+}
+
+fn execute_day5(tape: &[isize], io: FuzzIo, budget: usize) -> Execution {
+    use day5::intcode_machine::{IntcodeMachine, IntcodeMachineError, Tape};
+
+    let mut machine = IntcodeMachine::new(Tape::new(tape.to_vec()), io, FuzzIo::default());
+    let termination = drive(budget, || {
+        machine.step().map_err(|err| match err {
+            IntcodeMachineError::TapeOutOfBoundsError => ErrorKind::TapeOutOfBounds,
+            IntcodeMachineError::ExecutionFailure => ErrorKind::ExecutionFailure,
+            IntcodeMachineError::InputFailure => ErrorKind::InputFailure,
+        })
+    });
+
+    let memory = machine.tape().as_slice().to_vec();
+    Execution {
+        termination,
+        outputs: machine.into_output().outputs,
+        memory,
+    }
+}
+
+fn execute_day7(tape: &[isize], io: FuzzIo, budget: usize) -> Execution {
+    use day7::intcode_machine::{IntcodeMachine, IntcodeMachineError, Tape};
+
+    let mut machine = IntcodeMachine::new(Tape::new(tape.to_vec()), io, FuzzIo::default());
+    let termination = drive(budget, || {
+        machine.step().map_err(|err| match err {
+            IntcodeMachineError::TapeOutOfBoundsError => ErrorKind::TapeOutOfBounds,
+            IntcodeMachineError::ExecutionFailure => ErrorKind::ExecutionFailure,
+            IntcodeMachineError::InputFailure(_) => ErrorKind::InputFailure,
+        })
+    });
+
+    let memory = machine.dump_state().tape().as_slice().to_vec();
+    Execution {
+        termination,
+        outputs: machine.into_output().outputs,
+        memory,
+    }
+}
+
+// Decodes the instruction under the head to check whether executing it would make day9 allocate
+// an unreasonable amount of memory, which only large addresses can do. Negative ones are
+// rejected by day9 itself.
+fn day9_exceeds_memory_limit(state: &day9::intcode_machine::State) -> bool {
+    use day9::intcode_machine::{POSITION_MODE, RELATIVE_MODE};
+
+    let tape = state.tape().as_slice();
+    let cell = |address: usize| tape.get(address).cloned().unwrap_or(0);
+
+    let head = state.head_position();
+    let instruction = cell(head);
+    if instruction < 0 {
+        return false;
+    }
+
+    let mut modes = instruction / 100;
+    for i in 0..generator::params(instruction % 100).len() {
+        let mode = (modes % 10) as usize;
+        modes /= 10;
+
+        let operand = cell(head + 1 + i);
+        let address = match mode {
+            POSITION_MODE => operand,
+            // overflowing addresses are rejected by day9 itself
+            RELATIVE_MODE => match operand.checked_add(state.relative_base()) {
+                Some(address) => address,
+                None => return false,
+            },
+            _ => continue,
+        };
+        if address >= DAY9_MEMORY_LIMIT {
+            return true;
+        }
+    }
+
+    false
+}
+
+fn execute_day9(tape: &[isize], io: FuzzIo, budget: usize) -> Execution {
+    use day9::intcode_machine::{IntcodeMachine, IntcodeMachineError, StepOutcome, Tape};
+
+    let mut machine = IntcodeMachine::new(Tape::new(tape.to_vec()), io, FuzzIo::default());
+    let termination = drive(budget, || {
+        if day9_exceeds_memory_limit(&machine.dump_state()) {
+            return Err(ErrorKind::MemoryLimit);
+        }
+        match machine.step() {
+            Ok(StepOutcome::Executed) => Ok(None),
+            Ok(StepOutcome::Halted) => Ok(Some(machine.dump_state().tape().as_slice()[0])),
+            Ok(StepOutcome::AwaitingInput) => Err(ErrorKind::InputFailure),
+            Err(IntcodeMachineError::TapeOutOfBoundsError) => Err(ErrorKind::TapeOutOfBounds),
+            Err(IntcodeMachineError::ExecutionFailure) => Err(ErrorKind::ExecutionFailure),
+            Err(IntcodeMachineError::InputFailure(_)) | Err(IntcodeMachineError::InputError(_)) => {
+                Err(ErrorKind::InputFailure)
+            }
+            Err(IntcodeMachineError::UnsupportedInstruction(_)) => Err(ErrorKind::ExecutionFailure),
+        }
+    });
+
+    let memory = machine.dump_state().tape().as_slice().to_vec();
+    Execution {
+        termination,
+        outputs: std::mem::take(&mut machine.output_mut().outputs),
+        memory,
+    }
+}
+
+pub fn execute(interpreter: Interpreter, case: &FuzzCase, budget: usize) -> Execution {
+    let io = FuzzIo {
+        inputs: case.inputs.iter().cloned().collect(),
+        outputs: Vec::new(),
+    };
+
+    match interpreter {
+        Interpreter::Day5 => execute_day5(&case.tape, io, budget),
+        Interpreter::Day7 => execute_day7(&case.tape, io, budget),
+        Interpreter::Day9 => execute_day9(&case.tape, io, budget),
+    }
+}
+
+// day9 grows its tape on demand, so cells that were never touched are treated as zeroes
+fn same_memory(a: &[isize], b: &[isize]) -> bool {
+    let len = a.len().max(b.len());
+    (0..len).all(|i| a.get(i).unwrap_or(&0) == b.get(i).unwrap_or(&0))
+}
+
+pub fn compare(case: &FuzzCase, interpreters: &[Interpreter], budget: usize) -> Option<Divergence> {
+    let executions: Vec<_> = interpreters
+        .iter()
+        .map(|&interpreter| (interpreter, execute(interpreter, case, budget)))
+        .collect();
+
+    let (_, reference) = executions.first()?;
+    let mut kinds = Vec::new();
+    let others = || executions.iter().skip(1).map(|(_, execution)| execution);
+
+    if others().any(|execution| execution.termination != reference.termination) {
+        kinds.push(DivergenceKind::Termination);
+    }
+    if others().any(|execution| execution.outputs != reference.outputs) {
+        kinds.push(DivergenceKind::Outputs);
+    }
+    if others().any(|execution| !same_memory(&execution.memory, &reference.memory)) {
+        kinds.push(DivergenceKind::Memory);
+    }
+
+    if kinds.is_empty() {
+        None
+    } else {
+        Some(Divergence {
+            case: case.clone(),
+            kinds,
+            executions,
+        })
+    }
+}
+
+pub fn fuzz(
+    seed: u64,
+    iterations: usize,
+    config: &FuzzConfig,
+    interpreters: &[Interpreter],
+) -> Vec<Divergence> {
+    let mut rng = Rng::new(seed);
+    (0..iterations)
+        .filter_map(|_| {
+            let case = generator::generate(&mut rng, config);
+            compare(&case, interpreters, config.budget)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(tape: Vec<isize>, inputs: Vec<isize>) -> FuzzCase {
+        FuzzCase { tape, inputs }
+    }
+
+    #[test]
+    fn identical_behaviour_is_not_reported() {
+        let case = case(vec![3, 9, 1002, 9, 3, 9, 4, 9, 99, 0], vec![7]);
+        assert!(compare(&case, &ALL_INTERPRETERS, 100).is_none());
+
+        let execution = execute(Interpreter::Day5, &case, 100);
+        assert_eq!(Termination::Halted(3), execution.termination);
+        assert_eq!(vec![21], execution.outputs);
+    }
+
+    #[test]
+    fn reading_just_past_the_tape_is_flagged() {
+        // day5 and day7 reject reads beyond their fixed size tape, while day9 resizes it
+        let case = case(vec![4, 3, 99], vec![]);
+        let divergence = compare(&case, &ALL_INTERPRETERS, 100).unwrap();
+
+        assert!(divergence.kinds.contains(&DivergenceKind::Termination));
+        assert!(divergence.kinds.contains(&DivergenceKind::Outputs));
+
+        let terminations: Vec<_> = divergence
+            .executions
+            .iter()
+            .map(|(_, execution)| execution.termination)
+            .collect();
+        assert_eq!(
+            vec![
+                Termination::Failed(ErrorKind::ExecutionFailure),
+                Termination::Failed(ErrorKind::ExecutionFailure),
+                Termination::Halted(4),
+            ],
+            terminations
+        );
+    }
+
+    #[test]
+    fn budget_stops_infinite_loops() {
+        let case = case(vec![1105, 1, 0], vec![]);
+        for &interpreter in ALL_INTERPRETERS.iter() {
+            assert_eq!(
+                Termination::BudgetExhausted,
+                execute(interpreter, &case, 50).termination
+            );
+        }
+    }
+
+    #[test]
+    fn huge_allocations_are_prevented() {
+        // reads from address 2^40
+        let case = case(vec![4, 1 << 40, 99], vec![]);
+        assert_eq!(
+            Termination::Failed(ErrorKind::MemoryLimit),
+            execute(Interpreter::Day9, &case, 100).termination
+        );
+    }
+
+    #[test]
+    fn writes_to_negative_addresses_fail_everywhere() {
+        let case = case(vec![1101, 1, 1, -1, 99], vec![]);
+        assert!(compare(&case, &ALL_INTERPRETERS, 100).is_none());
+        assert_eq!(
+            Termination::Failed(ErrorKind::ExecutionFailure),
+            execute(Interpreter::Day9, &case, 100).termination
+        );
+    }
+
+    #[test]
+    fn missing_input_is_the_same_error_everywhere() {
+        let case = case(vec![3, 0, 99], vec![]);
+        assert!(compare(&case, &ALL_INTERPRETERS, 100).is_none());
+        assert_eq!(
+            Termination::Failed(ErrorKind::InputFailure),
+            execute(Interpreter::Day9, &case, 100).termination
+        );
+    }
+
+    #[test]
+    fn day5_and_day7_interpreters_agree_on_random_programs() {
+        // both share the same code, so any divergence here is a harness bug
+        let divergences = fuzz(
+            1,
+            200,
+            &FuzzConfig::default(),
+            &[Interpreter::Day5, Interpreter::Day7],
+        );
+        assert!(divergences.is_empty(), "{:?}", divergences.first());
+    }
+}
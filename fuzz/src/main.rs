@@ -0,0 +1,55 @@
+use std::env;
+use std::panic;
+
+use intcode_fuzz::generator::FuzzConfig;
+use intcode_fuzz::harness::{self, Divergence, ALL_INTERPRETERS};
+
+const DEFAULT_ITERATIONS: usize = 1000;
+
+fn print_divergence(divergence: &Divergence) {
+    println!("divergence in {:?}", divergence.kinds);
+    println!("  tape:   {:?}", divergence.case.tape);
+    println!("  inputs: {:?}", divergence.case.inputs);
+    for (interpreter, execution) in divergence.executions.iter() {
+        println!(
+            "  {:?}: {:?}, outputs: {:?}",
+            interpreter, execution.termination, execution.outputs
+        );
+    }
+}
+
+// usage: intcode_fuzz [iterations] [seed] [--relative-base]
+fn main() {
+    let args: Vec<_> = env::args().skip(1).collect();
+    let relative_base = args.iter().any(|arg| arg == "--relative-base");
+    let mut numbers = args.iter().filter(|arg| !arg.starts_with("--"));
+
+    let iterations = numbers
+        .next()
+        .map(|arg| arg.parse().expect("invalid number of iterations"))
+        .unwrap_or(DEFAULT_ITERATIONS);
+    let seed = numbers
+        .next()
+        .map(|arg| arg.parse().expect("invalid seed"))
+        .unwrap_or(0);
+
+    let config = FuzzConfig {
+        relative_base,
+        ..FuzzConfig::default()
+    };
+
+    // interpreters panicking on malformed tapes are expected and reported as divergences
+    panic::set_hook(Box::new(|_| ()));
+    let divergences = harness::fuzz(seed, iterations, &config, &ALL_INTERPRETERS);
+    let _ = panic::take_hook();
+
+    for divergence in divergences.iter() {
+        print_divergence(divergence);
+    }
+    println!(
+        "found {} divergences in {} cases (seed {})",
+        divergences.len(),
+        iterations,
+        seed
+    );
+}
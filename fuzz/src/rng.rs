@@ -0,0 +1,54 @@
+// Tiny xorshift64* generator. Fuzzing only needs cheap, reproducible randomness,
+// so that any divergence can be replayed from its seed.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // state of all zeroes would only ever produce zeroes
+        Rng(seed ^ 0x9E37_79B9_7F4A_7C15)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    // uniformly distributed value in [low, high)
+    pub fn range(&mut self, low: isize, high: isize) -> isize {
+        assert!(low < high, "empty range");
+        low + (self.next_u64() % (high - low) as u64) as isize
+    }
+
+    pub fn one_in(&mut self, n: u64) -> bool {
+        self.next_u64().is_multiple_of(n)
+    }
+
+    pub fn choose<'a, T>(&mut self, values: &'a [T]) -> &'a T {
+        &values[self.range(0, values.len() as isize) as usize]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_produces_same_sequence() {
+        let mut rng1 = Rng::new(42);
+        let mut rng2 = Rng::new(42);
+        for _ in 0..100 {
+            assert_eq!(rng1.next_u64(), rng2.next_u64());
+        }
+    }
+
+    #[test]
+    fn range_stays_within_bounds() {
+        let mut rng = Rng::new(0);
+        for _ in 0..1000 {
+            let value = rng.range(-3, 4);
+            assert!((-3..4).contains(&value));
+        }
+    }
+}
@@ -0,0 +1,209 @@
+use flate2::read::GzDecoder;
+use std::env;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::process;
+
+const STDIN_PATH: &str = "-";
+const COMMENT_START: char = '#';
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Debug)]
+pub enum ProgramLoadError {
+    IoError(io::Error),
+    InvalidValue {
+        line: usize,
+        column: usize,
+        token: String,
+    },
+    MissingValue {
+        line: usize,
+        column: usize,
+    },
+    EmptyProgram,
+}
+
+impl From<io::Error> for ProgramLoadError {
+    fn from(err: io::Error) -> Self {
+        ProgramLoadError::IoError(err)
+    }
+}
+
+impl fmt::Display for ProgramLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProgramLoadError::IoError(err) => write!(f, "failed to read the program: {}", err),
+            ProgramLoadError::InvalidValue {
+                line,
+                column,
+                token,
+            } => write!(f, "{}:{}: invalid value '{}'", line, column, token),
+            ProgramLoadError::MissingValue { line, column } => {
+                write!(f, "{}:{}: missing value", line, column)
+            }
+            ProgramLoadError::EmptyProgram => write!(f, "program does not contain any values"),
+        }
+    }
+}
+
+// Values are separated with commas and can be spread over multiple lines.
+// Anything following '#' is a comment. Lines and columns are counted from 1.
+pub fn parse_program(source: &str) -> Result<Vec<isize>, ProgramLoadError> {
+    let mut program = Vec::new();
+
+    for (line_idx, line) in source.lines().enumerate() {
+        let code = match line.find(COMMENT_START) {
+            Some(comment_start) => &line[..comment_start],
+            None => line,
+        };
+        if code.trim().is_empty() {
+            continue;
+        }
+
+        let fields: Vec<_> = code.split(',').collect();
+        let mut column = 1;
+        for (field_idx, field) in fields.iter().enumerate() {
+            let token = field.trim();
+            let token_column = column + field.chars().take_while(|c| c.is_whitespace()).count();
+            column += field.chars().count() + 1;
+
+            if token.is_empty() {
+                // trailing comma is fine, as the program might continue on the next line
+                if field_idx == fields.len() - 1 {
+                    continue;
+                }
+                return Err(ProgramLoadError::MissingValue {
+                    line: line_idx + 1,
+                    column: token_column,
+                });
+            }
+
+            match token.parse::<isize>() {
+                Ok(value) => program.push(value),
+                Err(_) => {
+                    return Err(ProgramLoadError::InvalidValue {
+                        line: line_idx + 1,
+                        column: token_column,
+                        token: token.to_string(),
+                    })
+                }
+            }
+        }
+    }
+
+    if program.is_empty() {
+        return Err(ProgramLoadError::EmptyProgram);
+    }
+    Ok(program)
+}
+
+// gzip compressed data is recognised by its header rather than the file extension
+pub fn read_program<R: Read>(mut reader: R) -> Result<Vec<isize>, ProgramLoadError> {
+    let mut raw = Vec::new();
+    reader.read_to_end(&mut raw)?;
+
+    let mut source = String::new();
+    if raw.starts_with(&GZIP_MAGIC) {
+        GzDecoder::new(&raw[..]).read_to_string(&mut source)?;
+    } else {
+        source = String::from_utf8(raw)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    }
+
+    parse_program(&source)
+}
+
+// "-" denotes the standard input
+pub fn load_program(path: &str) -> Result<Vec<isize>, ProgramLoadError> {
+    if path == STDIN_PATH {
+        read_program(io::stdin().lock())
+    } else {
+        read_program(File::open(path)?)
+    }
+}
+
+// Program given as the first argument or the default one otherwise, "-" reads it from the
+// standard input. Any failure is reported and terminates the process.
+pub fn load_program_from_args(default_path: &str) -> Vec<isize> {
+    let path = env::args()
+        .nth(1)
+        .unwrap_or_else(|| default_path.to_string());
+
+    match load_program(&path) {
+        Ok(program) => program,
+        Err(err) => {
+            eprintln!("{}: {}", path, err);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    #[test]
+    fn program_can_span_multiple_lines_with_comments() {
+        let source = "# day2 example\n1,9,10,3,  # add\n 2,3,11,0,\n\n99,30,40,50\n";
+
+        assert_eq!(
+            vec![1, 9, 10, 3, 2, 3, 11, 0, 99, 30, 40, 50],
+            parse_program(source).unwrap()
+        );
+    }
+
+    #[test]
+    fn invalid_value_is_reported_with_its_location() {
+        match parse_program("1,0,0,3\n99, 3x,4\n") {
+            Err(ProgramLoadError::InvalidValue {
+                line,
+                column,
+                token,
+            }) => {
+                assert_eq!((2, 5), (line, column));
+                assert_eq!("3x", token);
+            }
+            other => panic!("expected invalid value error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn missing_value_is_reported_with_its_location() {
+        match parse_program("1,,0,3,99") {
+            Err(ProgramLoadError::MissingValue { line, column }) => {
+                assert_eq!((1, 3), (line, column))
+            }
+            other => panic!("expected missing value error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn program_without_values_is_rejected() {
+        for source in ["", "\n  \n", "# nothing here\n"].iter() {
+            match parse_program(source) {
+                Err(ProgramLoadError::EmptyProgram) => (),
+                other => panic!("expected empty program error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn gzip_compressed_program_is_decompressed() {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(b"104,1125899906842624,99\n").unwrap();
+        let compressed = encoder.finish().unwrap();
+
+        assert_eq!(
+            vec![104, 1_125_899_906_842_624, 99],
+            read_program(&compressed[..]).unwrap()
+        );
+        assert_eq!(
+            vec![104, 1_125_899_906_842_624, 99],
+            read_program(&b"104,1125899906842624,99"[..]).unwrap()
+        );
+    }
+}
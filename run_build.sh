@@ -8,3 +8,9 @@ for i in {1..25}; do
             echo "No solution for day $i found"
     fi 
 done
+
+cargo build --manifest-path=loader/Cargo.toml --verbose --all
+cargo test --manifest-path=loader/Cargo.toml --verbose --all
+
+cargo build --manifest-path=fuzz/Cargo.toml --verbose --all
+cargo test --manifest-path=fuzz/Cargo.toml --verbose --all